@@ -19,6 +19,7 @@ pub enum Expr {
     If(Vec<Box<IfBranch>>),
     While(Box<Expr>, Vec<Box<Expr>>),
     For(Box<Expr>, Box<Expr>, Box<Expr>, Vec<Box<Expr>>),
+    ForIn(String, Box<Expr>, Vec<Box<Expr>>),
     Return(Box<Expr>),
     Import(String, String),
 }
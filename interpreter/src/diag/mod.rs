@@ -1,11 +1,22 @@
-use std::ops::Range;
+use std::{fmt::Display, ops::Range, time::Duration};
 
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ShiroError {
     ModuleNotFound {
         path: String,
+        tried: Vec<String>,
+    },
+    ModuleDisabled {
+        path: String,
+    },
+    ImportCycle {
+        chain: Vec<String>,
+    },
+    ManifestError {
+        path: String,
+        message: String,
     },
     InvalidToken {
         file_id: usize,
@@ -30,6 +41,57 @@ pub enum ShiroError {
     },
     GenericParserError(String),
     UnknownInstruction,
+    StackOverflow {
+        depth: usize,
+    },
+    StepLimitExceeded {
+        limit: u64,
+    },
+    HeapLimitExceeded {
+        limit: usize,
+        unit: &'static str,
+    },
+    Timeout {
+        limit: Duration,
+    },
+    DanglingReference {
+        address: String,
+    },
+    ArityMismatch {
+        function: String,
+        expected: String,
+        got: usize,
+    },
+    TypeMismatch {
+        expected: String,
+        got: String,
+    },
+    ArgumentType {
+        function: String,
+        position: usize,
+        expected: String,
+        got: String,
+    },
+    ConversionError(String),
+    Interrupted,
+    UndefinedVariable {
+        name: String,
+    },
+    AssertionFailed {
+        message: String,
+    },
+    Exit {
+        code: i32,
+    },
+    IoError {
+        path: String,
+        message: String,
+    },
+    JsonError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
     GenericRuntimeError(String),
 }
 
@@ -37,26 +99,83 @@ impl ShiroError {
     pub fn error_code(&self) -> String {
         match self {
             ShiroError::ModuleNotFound { .. } => "E0101",
+            ShiroError::ModuleDisabled { .. } => "E0102",
+            ShiroError::ImportCycle { .. } => "E0103",
+            ShiroError::ManifestError { .. } => "E0104",
             ShiroError::InvalidToken { .. } => "E0201",
             ShiroError::UnrecognizedEOF { .. } => "E0202",
             ShiroError::UnrecognizedToken { .. } => "E0203",
             ShiroError::ExtraToken { .. } => "E0204",
             ShiroError::GenericParserError(_) => "E0299",
             ShiroError::UnknownInstruction => "E0301",
+            ShiroError::StackOverflow { .. } => "E0302",
+            ShiroError::StepLimitExceeded { .. } => "E0303",
+            ShiroError::HeapLimitExceeded { .. } => "E0304",
+            ShiroError::Timeout { .. } => "E0305",
+            ShiroError::DanglingReference { .. } => "E0306",
+            ShiroError::ArityMismatch { .. } => "E0307",
+            ShiroError::TypeMismatch { .. } => "E0308",
+            ShiroError::ArgumentType { .. } => "E0309",
+            ShiroError::ConversionError(_) => "E0310",
+            ShiroError::Interrupted => "E0311",
+            ShiroError::UndefinedVariable { .. } => "E0312",
+            ShiroError::AssertionFailed { .. } => "E0313",
+            ShiroError::Exit { .. } => "E0314",
+            ShiroError::IoError { .. } => "E0315",
+            ShiroError::JsonError { .. } => "E0316",
             ShiroError::GenericRuntimeError(_) => "E0399",
         }
         .to_string()
     }
 }
 
+impl Display for ShiroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let diag: Diagnostic<usize> = self.clone().into();
+        write!(f, "error[{}]: {}", self.error_code(), diag.message)?;
+        for note in diag.notes {
+            write!(f, " ({})", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShiroError {}
+
+impl From<String> for ShiroError {
+    fn from(err: String) -> Self {
+        ShiroError::GenericRuntimeError(err)
+    }
+}
+
+impl From<&str> for ShiroError {
+    fn from(err: &str) -> Self {
+        ShiroError::GenericRuntimeError(err.to_string())
+    }
+}
+
 impl Into<Diagnostic<usize>> for ShiroError {
     fn into(self) -> Diagnostic<usize> {
         let diag = Diagnostic::error().with_code(self.error_code());
 
         match self {
-            ShiroError::ModuleNotFound { path } => {
-                diag.with_message(format!("Module at `{}` not found", path))
+            ShiroError::ModuleNotFound { path, tried } => diag
+                .with_message(format!("Module at `{}` not found", path))
+                .with_notes(
+                    tried
+                        .iter()
+                        .map(|location| format!("Tried `{}`", location))
+                        .collect(),
+                ),
+            ShiroError::ModuleDisabled { path } => {
+                diag.with_message(format!("Module `{}` is disabled in this runtime", path))
             }
+            ShiroError::ImportCycle { chain } => diag
+                .with_message("Import cycle detected")
+                .with_notes(vec![format!("Import chain: {}", chain.join(" -> "))]),
+            ShiroError::ManifestError { path, message } => diag
+                .with_message(format!("Invalid package manifest `{}`", path))
+                .with_notes(vec![message]),
             ShiroError::InvalidToken {
                 file_id,
                 range,
@@ -92,6 +211,77 @@ impl Into<Diagnostic<usize>> for ShiroError {
                 diag.with_message(format!("Parser error: {}", err))
             }
             ShiroError::UnknownInstruction => diag.with_message("Unknown instruction"),
+            ShiroError::StackOverflow { depth } => diag
+                .with_message("Stack overflow")
+                .with_notes(vec![format!("Maximum call depth of {} exceeded", depth)]),
+            ShiroError::StepLimitExceeded { limit } => diag
+                .with_message("Step limit exceeded")
+                .with_notes(vec![format!(
+                    "Evaluation was stopped after {} steps",
+                    limit
+                )]),
+            ShiroError::HeapLimitExceeded { limit, unit } => diag
+                .with_message("Heap limit exceeded")
+                .with_notes(vec![format!(
+                    "The heap may not hold more than {} {}",
+                    limit, unit
+                )]),
+            ShiroError::Timeout { limit } => {
+                diag.with_message("Timeout").with_notes(vec![format!(
+                    "Evaluation did not finish within {:?}",
+                    limit
+                )])
+            }
+            ShiroError::DanglingReference { address } => diag.with_message(format!(
+                "Object at heap address `{}` no longer exists",
+                address
+            )),
+            ShiroError::ArityMismatch {
+                function,
+                expected,
+                got,
+            } => diag.with_message(format!(
+                "Function `{}` expects {}, but got {}",
+                function, expected, got
+            )),
+            ShiroError::TypeMismatch { expected, got } => {
+                diag.with_message(format!("Expected {}, but got {}", expected, got))
+            }
+            ShiroError::ArgumentType {
+                function,
+                position,
+                expected,
+                got,
+            } => diag.with_message(format!(
+                "Argument {} of function `{}` must be {}, but got {}",
+                position, function, expected, got
+            )),
+            ShiroError::ConversionError(err) => {
+                diag.with_message(format!("Conversion error: {}", err))
+            }
+            ShiroError::Interrupted => diag
+                .with_message("Interrupted")
+                .with_notes(vec!["Evaluation was stopped by the host".to_string()]),
+            ShiroError::UndefinedVariable { name } => {
+                diag.with_message(format!("Variable `{}` is not defined", name))
+            }
+            ShiroError::AssertionFailed { message } => {
+                diag.with_message(format!("Assertion failed: {}", message))
+            }
+            ShiroError::Exit { code } => {
+                diag.with_message(format!("Script exited with code {}", code))
+            }
+            ShiroError::IoError { path, message } => {
+                diag.with_message(format!("I/O error on `{}`: {}", path, message))
+            }
+            ShiroError::JsonError {
+                line,
+                column,
+                message,
+            } => diag.with_message(format!(
+                "Invalid JSON at line {}, column {}: {}",
+                line, column, message
+            )),
             ShiroError::GenericRuntimeError(err) => {
                 diag.with_message(format!("Runtime error: {}", err))
             }
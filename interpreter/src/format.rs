@@ -0,0 +1,91 @@
+const INDENT: &str = "    ";
+
+/// Where the scanner is within the code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    String,
+    LineComment,
+    BlockComment(usize),
+}
+
+/// Formats Shiro code by re-indenting every line by its nesting depth,
+/// trimming trailing whitespace and collapsing runs of blank lines. Only
+/// whitespace between tokens is touched, so comments are kept and the
+/// meaning of the code never changes.
+pub fn format_code(code: &str) -> String {
+    let mut result = String::new();
+    let mut state = State::Code;
+    let mut depth: usize = 0;
+    let mut blank_lines = 0;
+
+    for line in code.lines() {
+        let line = line.trim_end();
+        if state == State::LineComment {
+            state = State::Code;
+        }
+
+        if line.trim().is_empty() && state == State::Code {
+            blank_lines += 1;
+            continue;
+        }
+        if !result.is_empty() && blank_lines > 0 {
+            result.push('\n');
+        }
+        blank_lines = 0;
+
+        if state == State::Code {
+            // Closing brackets at the start of a line belong to the outer level
+            let trimmed = line.trim_start();
+            let closers = trimmed
+                .chars()
+                .take_while(|c| matches!(c, '}' | ']' | ')'))
+                .count();
+            for _ in 0..depth.saturating_sub(closers) {
+                result.push_str(INDENT);
+            }
+            result.push_str(trimmed);
+        } else {
+            // Lines inside strings and block comments are kept as they are
+            result.push_str(line);
+        }
+        result.push('\n');
+
+        let mut chars = line.chars().peekable();
+        while let Some(cur) = chars.next() {
+            let next = chars.peek().copied();
+            state = match (state, cur) {
+                (State::Code, '\'') => State::String,
+                (State::Code, '[') if next == Some('#') => {
+                    chars.next();
+                    State::BlockComment(1)
+                }
+                (State::Code, '#') => State::LineComment,
+                (State::Code, '{' | '[' | '(') => {
+                    depth += 1;
+                    State::Code
+                }
+                (State::Code, '}' | ']' | ')') => {
+                    depth = depth.saturating_sub(1);
+                    State::Code
+                }
+                (State::String, '\'') => State::Code,
+                (State::BlockComment(level), '[') if next == Some('#') => {
+                    chars.next();
+                    State::BlockComment(level + 1)
+                }
+                (State::BlockComment(level), '#') if next == Some(']') => {
+                    chars.next();
+                    if level == 1 {
+                        State::Code
+                    } else {
+                        State::BlockComment(level - 1)
+                    }
+                }
+                (state, _) => state,
+            };
+        }
+    }
+
+    result
+}
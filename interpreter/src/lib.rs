@@ -0,0 +1,34 @@
+use lalrpop_util::lalrpop_mod;
+
+// The grammar builds boxed expressions and the tree keeps that shape
+#[allow(clippy::vec_box)]
+mod ast;
+mod diag;
+mod format;
+mod package;
+mod parser;
+mod runtime;
+mod stdlib;
+
+lalrpop_mod!(shiro);
+
+pub use diag::ShiroError;
+pub use parser::CodeFile;
+pub use runtime::{
+    convert::{FromShiro, IntoNativeFunction, IntoShiro},
+    heap::{Heap, HeapAddr},
+    native::{Arity, NativeFunction, NativeLibProvider},
+    serialize::{from_value, to_value},
+    userdata::{UserData, UserDataType},
+    value::ShiroValue,
+    InterruptHandle, Runtime,
+};
+
+/// What the `shiro` command line tool needs beyond the embedding API. This
+/// is not a stable interface.
+#[doc(hidden)]
+pub mod cli {
+    pub use crate::format::format_code;
+    pub use crate::package::{Manifest, MANIFEST_FILE};
+    pub use crate::parser::{parse, Chunk};
+}
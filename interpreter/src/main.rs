@@ -1,39 +1,314 @@
-use std::env;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-use diag::ShiroError;
-use lalrpop_util::lalrpop_mod;
-use runtime::{value::ShiroValue, Runtime};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 
-use crate::parser::CodeFile;
+mod repl;
+mod testing;
 
-pub mod ast;
-mod diag;
-mod parser;
-mod runtime;
-mod stdlib;
+use shiro_interpreter::{
+    cli::{format_code, Manifest, MANIFEST_FILE},
+    CodeFile, Runtime, ShiroError, ShiroValue,
+};
 
-lalrpop_mod!(pub shiro);
+fn script_args() -> Arg {
+    Arg::new("args")
+        .help("Arguments passed to the script")
+        .num_args(0..)
+        .last(true)
+}
 
-fn run_file(rt: &mut Runtime, path: &str) -> Result<ShiroValue, ShiroError> {
-    let file = CodeFile::open(path)?;
-    rt.eval(file)
+fn file_arg() -> Arg {
+    Arg::new("file").help("Script file or package directory to run")
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("shiro: No input file specified");
-        return;
-    }
+fn cli() -> Command {
+    Command::new("shiro")
+        .bin_name("shiro")
+        .about("Runs Shiro scripts")
+        .version(env!("CARGO_PKG_VERSION"))
+        .args_conflicts_with_subcommands(true)
+        .arg(
+            Arg::new("lib-path")
+                .short('L')
+                .long("lib-path")
+                .help("Adds a directory to search for `@` modules")
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .global(true),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Makes using undefined variables an error")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("max-steps")
+                .long("max-steps")
+                .help("Stops scripts after this many evaluation steps")
+                .value_name("STEPS")
+                .value_parser(value_parser!(u64))
+                .global(true),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help("Stops scripts after this many milliseconds")
+                .value_name("MS")
+                .value_parser(value_parser!(u64))
+                .global(true),
+        )
+        .arg(
+            Arg::new("max-call-depth")
+                .long("max-call-depth")
+                .help("Limits how deeply functions may be nested")
+                .value_name("DEPTH")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(
+            Arg::new("max-heap-objects")
+                .long("max-heap-objects")
+                .help("Limits how many objects may be alive at once")
+                .value_name("COUNT")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(
+            Arg::new("max-heap-bytes")
+                .long("max-heap-bytes")
+                .help("Limits the approximate size of all objects")
+                .value_name("BYTES")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(file_arg())
+        .arg(script_args())
+        .subcommand(
+            Command::new("run")
+                .about("Runs a script, or the package in the current directory")
+                .arg(file_arg())
+                .arg(script_args()),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Checks scripts for syntax errors without running them")
+                .arg(Arg::new("files").required(true).num_args(1..)),
+        )
+        .subcommand(
+            Command::new("eval")
+                .about("Evaluates code given on the command line")
+                .arg(
+                    Arg::new("expr")
+                        .short('e')
+                        .long("expr")
+                        .help("The code to evaluate")
+                        .value_name("CODE")
+                        .required(true),
+                )
+                .arg(script_args()),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Formats scripts in place")
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Only reports files that are not formatted")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(Arg::new("files").required(true).num_args(1..)),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Runs the `test_` functions of the scripts in the given paths")
+                .arg(
+                    Arg::new("paths")
+                        .num_args(0..)
+                        .default_value("tests")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(Command::new("repl").about("Starts an interactive session"))
+}
 
+/// Creates a runtime configured by the global flags.
+fn new_runtime(matches: &ArgMatches) -> Runtime {
     let mut rt = Runtime::new();
-    let result = run_file(&mut rt, &args[1]);
+    // Directories given on the command line take precedence
+    let mut lib_paths: Vec<PathBuf> = matches
+        .get_many::<PathBuf>("lib-path")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    lib_paths.append(&mut rt.lib_paths);
+    rt.lib_paths = lib_paths;
+
+    rt.strict = matches.get_flag("strict");
+    // The script is the only thing running in this process
+    rt.allow_process_changes = true;
+    rt.max_steps = matches.get_one::<u64>("max-steps").copied();
+    rt.timeout = matches
+        .get_one::<u64>("timeout")
+        .map(|ms| Duration::from_millis(*ms));
+    if let Some(depth) = matches.get_one::<usize>("max-call-depth") {
+        rt.max_call_depth = *depth;
+    }
+    rt.heap
+        .set_max_objects(matches.get_one::<usize>("max-heap-objects").copied());
+    rt.heap
+        .set_max_bytes(matches.get_one::<usize>("max-heap-bytes").copied());
+
+    rt.args = matches
+        .try_get_many::<String>("args")
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    rt
+}
 
+fn finish(rt: &Runtime, result: Result<ShiroValue, ShiroError>) -> ExitCode {
     match result {
-        Ok(result) => match result {
-            ShiroValue::Null => {}
-            _ => println!("\n{}", result.coerce_string()),
-        },
-        Err(error) => rt.report_error(error),
+        Ok(ShiroValue::Null) => ExitCode::SUCCESS,
+        Ok(result) => {
+            println!("\n{}", result.coerce_string());
+            ExitCode::SUCCESS
+        }
+        Err(ShiroError::Exit { code }) => exit_code(code),
+        Err(error) => {
+            rt.report_error(error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Converts the code passed to `os.exit` to a process exit status. Codes
+/// outside of 0 to 255 cannot be reported as they are and become 1.
+fn exit_code(code: i32) -> ExitCode {
+    u8::try_from(code).map_or(ExitCode::FAILURE, ExitCode::from)
+}
+
+/// Runs a script file, or the package in a directory. Without a file, the
+/// package in the current directory is run, or else the REPL is started.
+fn run(matches: &ArgMatches) -> ExitCode {
+    let mut rt = new_runtime(matches);
+    let result = match matches.get_one::<String>("file") {
+        Some(file) if !Path::new(file).is_dir() => {
+            let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            match Manifest::find(&dir.canonicalize().unwrap_or(dir.to_path_buf())) {
+                Some(manifest) => rt.load_manifest(&manifest).and_then(|_| rt.eval_path(file)),
+                None => rt.eval_path(file),
+            }
+        }
+        Some(dir) => {
+            let manifest = PathBuf::from(dir).join(MANIFEST_FILE);
+            if !manifest.is_file() {
+                eprintln!("shiro: No {} found in `{}`", MANIFEST_FILE, dir);
+                return ExitCode::FAILURE;
+            }
+            rt.eval_package(&manifest)
+        }
+        None => {
+            let manifest = PathBuf::from(MANIFEST_FILE);
+            if !manifest.is_file() {
+                return repl::run(rt);
+            }
+            rt.eval_package(&manifest)
+        }
+    };
+    finish(&rt, result)
+}
+
+fn check(matches: &ArgMatches) -> ExitCode {
+    let mut rt = new_runtime(matches);
+    let mut status = ExitCode::SUCCESS;
+    for file in matches.get_many::<String>("files").unwrap() {
+        if let Err(error) = CodeFile::open(file).and_then(|code| rt.check(code)) {
+            rt.report_error(error);
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn eval(matches: &ArgMatches) -> ExitCode {
+    let mut rt = new_runtime(matches);
+    let code = matches.get_one::<String>("expr").unwrap();
+    let result = rt.eval_str("<eval>", code);
+    finish(&rt, result)
+}
+
+fn fmt(matches: &ArgMatches) -> ExitCode {
+    let mut rt = new_runtime(matches);
+    let check_only = matches.get_flag("check");
+    let mut status = ExitCode::SUCCESS;
+    for file in matches.get_many::<String>("files").unwrap() {
+        let code = match fs::read_to_string(file) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("shiro: Cannot read `{}`: {}", file, err);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        // Code that does not parse is left alone
+        if let Err(error) = rt.check(CodeFile::new(file, &code)) {
+            rt.report_error(error);
+            status = ExitCode::FAILURE;
+            continue;
+        }
+
+        let formatted = format_code(&code);
+        if formatted == code {
+            continue;
+        }
+        if check_only {
+            println!("{} is not formatted", file);
+            status = ExitCode::FAILURE;
+        } else if let Err(err) = fs::write(file, formatted) {
+            eprintln!("shiro: Cannot write `{}`: {}", file, err);
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+fn test(matches: &ArgMatches) -> ExitCode {
+    let paths: Vec<PathBuf> = matches
+        .get_many::<PathBuf>("paths")
+        .unwrap()
+        .cloned()
+        .collect();
+    if testing::run(&paths, || new_runtime(matches)) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    match matches.subcommand() {
+        Some(("run", sub)) => run(sub),
+        Some(("check", sub)) => check(sub),
+        Some(("eval", sub)) => eval(sub),
+        Some(("fmt", sub)) => fmt(sub),
+        Some(("test", sub)) => test(sub),
+        Some(("repl", sub)) => repl::run(new_runtime(sub)),
+        _ => run(&matches),
     }
 }
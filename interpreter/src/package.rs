@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+
+use crate::diag::ShiroError;
+
+pub const MANIFEST_FILE: &str = "shiro.toml";
+pub const LOCK_FILE: &str = "shiro.lock";
+
+const DEFAULT_ENTRY: &str = "main.shiro";
+const DEFAULT_VENDOR_DIR: &str = "vendor";
+
+/// The contents of a `shiro.toml` file.
+///
+/// ```toml
+/// [package]
+/// name = "app"
+/// version = "0.1.0"
+/// entry = "main.shiro"
+///
+/// [dependencies]
+/// utils = { path = "../utils" }
+/// json = "1.2.0"  # looked up in the vendor directory
+/// ```
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub entry: String,
+    /// Where dependencies without a path are looked up, relative to the manifest.
+    pub vendor_dir: String,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub source: DependencySource,
+}
+
+#[derive(Debug, Clone)]
+pub enum DependencySource {
+    /// A package directory, relative to the manifest declaring it.
+    Path(PathBuf),
+    /// A package in the vendor directory of the root package.
+    Vendored { version: Option<String> },
+}
+
+fn manifest_error(path: &Path, message: impl Into<String>) -> ShiroError {
+    ShiroError::ManifestError {
+        path: path.display().to_string(),
+        message: message.into(),
+    }
+}
+
+fn get_str(table: &Table, key: &str, path: &Path) -> Result<Option<String>, ShiroError> {
+    match table.get(key) {
+        Some(Value::String(str)) => Ok(Some(str.clone())),
+        Some(_) => Err(manifest_error(path, format!("`{}` must be a string", key))),
+        None => Ok(None),
+    }
+}
+
+impl Manifest {
+    pub fn open(path: &Path) -> Result<Manifest, ShiroError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| manifest_error(path, format!("Cannot read manifest: {}", err)))?;
+        Manifest::parse(path, &content)
+    }
+
+    /// Parses a manifest, using `path` to refer to it in errors.
+    pub fn parse(path: &Path, content: &str) -> Result<Manifest, ShiroError> {
+        let table: Table = content
+            .parse()
+            .map_err(|err: toml::de::Error| manifest_error(path, err.message()))?;
+
+        let package = match table.get("package") {
+            Some(Value::Table(package)) => package,
+            _ => return Err(manifest_error(path, "Missing `[package]` section")),
+        };
+        let name = get_str(package, "name", path)?
+            .ok_or_else(|| manifest_error(path, "Missing package name"))?;
+        let version = get_str(package, "version", path)?
+            .ok_or_else(|| manifest_error(path, "Missing package version"))?;
+        let entry = get_str(package, "entry", path)?.unwrap_or(DEFAULT_ENTRY.to_string());
+        let vendor_dir =
+            get_str(package, "vendor", path)?.unwrap_or(DEFAULT_VENDOR_DIR.to_string());
+
+        let mut dependencies = Vec::new();
+        if let Some(deps) = table.get("dependencies") {
+            let Value::Table(deps) = deps else {
+                return Err(manifest_error(path, "`[dependencies]` must be a table"));
+            };
+            for (dep_name, spec) in deps {
+                let source = match spec {
+                    Value::String(version) => DependencySource::Vendored {
+                        version: Some(version.clone()),
+                    },
+                    Value::Table(spec) => match get_str(spec, "path", path)? {
+                        Some(dep_path) => DependencySource::Path(PathBuf::from(dep_path)),
+                        None => DependencySource::Vendored {
+                            version: get_str(spec, "version", path)?,
+                        },
+                    },
+                    _ => {
+                        return Err(manifest_error(
+                            path,
+                            format!("Invalid specification for dependency `{}`", dep_name),
+                        ))
+                    }
+                };
+                dependencies.push(Dependency {
+                    name: dep_name.clone(),
+                    source,
+                });
+            }
+        }
+
+        Ok(Manifest {
+            name,
+            version,
+            entry,
+            vendor_dir,
+            dependencies,
+        })
+    }
+
+    /// Looks for a manifest in `dir` and its ancestors.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE))
+            .find(|path| path.is_file())
+    }
+}
+
+/// A package of the dependency graph, with all paths made canonical.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    /// Where the package comes from, as written to the lockfile.
+    pub source: String,
+    /// Maps dependency names to indices into [`PackageGraph::packages`].
+    pub dependencies: HashMap<String, usize>,
+}
+
+/// All packages reachable from a root package. Every package directory
+/// appears once, no matter how many packages depend on it.
+#[derive(Debug, Clone)]
+pub struct PackageGraph {
+    /// The root package comes first.
+    pub packages: Vec<Package>,
+}
+
+impl PackageGraph {
+    /// Reads the manifest at `manifest_path` and the manifests of all of its
+    /// dependencies, transitively.
+    pub fn resolve(manifest_path: &Path) -> Result<PackageGraph, ShiroError> {
+        let root_dir = package_dir(manifest_path)?;
+        let manifest = Manifest::open(manifest_path)?;
+        let vendor_dir = root_dir.join(&manifest.vendor_dir);
+
+        let mut graph = PackageGraph {
+            packages: Vec::new(),
+        };
+        graph.add_package(
+            manifest,
+            root_dir.clone(),
+            "root".to_string(),
+            &root_dir,
+            &vendor_dir,
+        )?;
+        Ok(graph)
+    }
+
+    fn add_package(
+        &mut self,
+        manifest: Manifest,
+        dir: PathBuf,
+        source: String,
+        root_dir: &Path,
+        vendor_dir: &Path,
+    ) -> Result<usize, ShiroError> {
+        let index = self.packages.len();
+        self.packages.push(Package {
+            name: manifest.name,
+            version: manifest.version,
+            entry: dir.join(&manifest.entry),
+            root: dir.clone(),
+            source,
+            dependencies: HashMap::new(),
+        });
+
+        for dep in manifest.dependencies {
+            let (dep_dir, kind) = match &dep.source {
+                DependencySource::Path(path) => (dir.join(path), "path"),
+                DependencySource::Vendored { .. } => (vendor_dir.join(&dep.name), "vendor"),
+            };
+            let dep_manifest_path = dep_dir.join(MANIFEST_FILE);
+            if !dep_manifest_path.is_file() {
+                return Err(manifest_error(
+                    &dir.join(MANIFEST_FILE),
+                    format!(
+                        "Dependency `{}` not found at `{}`",
+                        dep.name,
+                        dep_dir.display()
+                    ),
+                ));
+            }
+            let dep_dir = package_dir(&dep_manifest_path)?;
+
+            let dep_index = match self.packages.iter().position(|p| p.root == dep_dir) {
+                Some(existing) => existing,
+                None => {
+                    let dep_manifest = Manifest::open(&dep_manifest_path)?;
+                    if dep_manifest.name != dep.name {
+                        return Err(manifest_error(
+                            &dep_manifest_path,
+                            format!(
+                                "Expected package `{}`, but found `{}`",
+                                dep.name, dep_manifest.name
+                            ),
+                        ));
+                    }
+                    if let DependencySource::Vendored {
+                        version: Some(version),
+                    } = &dep.source
+                    {
+                        if *version != dep_manifest.version {
+                            return Err(manifest_error(
+                                &dep_manifest_path,
+                                format!(
+                                    "Expected version {} of `{}`, but the vendored version is {}",
+                                    version, dep.name, dep_manifest.version
+                                ),
+                            ));
+                        }
+                    }
+                    let source =
+                        format!("{}+{}", kind, relative_path(&dep_dir, root_dir).display());
+                    self.add_package(dep_manifest, dep_dir, source, root_dir, vendor_dir)?
+                }
+            };
+            self.packages[index]
+                .dependencies
+                .insert(dep.name.clone(), dep_index);
+        }
+
+        Ok(index)
+    }
+
+    pub fn root(&self) -> &Package {
+        &self.packages[0]
+    }
+
+    /// Finds the package a file belongs to, i.e. the one with the innermost
+    /// root directory containing it.
+    pub fn package_of(&self, file: &Path) -> Option<&Package> {
+        self.packages
+            .iter()
+            .filter(|p| file.starts_with(&p.root))
+            .max_by_key(|p| p.root.components().count())
+    }
+
+    /// Looks up the dependency called `name` of the given package.
+    pub fn dependency(&self, package: &Package, name: &str) -> Option<&Package> {
+        package.dependencies.get(name).map(|&i| &self.packages[i])
+    }
+
+    /// The lockfile entries, which pin the resolved version and location of
+    /// every package in the graph.
+    fn lock_entries(&self) -> Vec<Value> {
+        let mut packages: Vec<&Package> = self.packages.iter().collect();
+        packages[1..].sort_by(|a, b| (&a.name, &a.source).cmp(&(&b.name, &b.source)));
+
+        packages
+            .into_iter()
+            .map(|package| {
+                let mut deps: Vec<&String> = package.dependencies.keys().collect();
+                deps.sort();
+
+                let mut entry = Table::new();
+                entry.insert("name".to_string(), Value::String(package.name.clone()));
+                entry.insert(
+                    "version".to_string(),
+                    Value::String(package.version.clone()),
+                );
+                entry.insert("source".to_string(), Value::String(package.source.clone()));
+                entry.insert(
+                    "dependencies".to_string(),
+                    Value::Array(deps.into_iter().map(|d| Value::String(d.clone())).collect()),
+                );
+                Value::Table(entry)
+            })
+            .collect()
+    }
+
+    /// Renders the lockfile.
+    pub fn lockfile(&self) -> String {
+        let mut lock = Table::new();
+        lock.insert("package".to_string(), Value::Array(self.lock_entries()));
+        format!(
+            "# This file is generated by shiro. Do not edit it by hand.\n{}",
+            lock
+        )
+    }
+
+    /// Checks the graph against the lockfile next to the root manifest, if
+    /// there is one. A package whose version differs from the locked one is
+    /// an error, while packages that were added or removed are not.
+    pub fn verify_lockfile(&self) -> Result<(), ShiroError> {
+        let path = self.root().root.join(LOCK_FILE);
+        let Some(entries) = read_lockfile(&path)? else {
+            return Ok(());
+        };
+
+        for entry in &entries {
+            let Value::Table(entry) = entry else {
+                return Err(manifest_error(&path, "Invalid package entry"));
+            };
+            let (Some(name), Some(version), Some(source)) = (
+                get_str(entry, "name", &path)?,
+                get_str(entry, "version", &path)?,
+                get_str(entry, "source", &path)?,
+            ) else {
+                return Err(manifest_error(
+                    &path,
+                    "Package entries need a name, version and source",
+                ));
+            };
+            let resolved = self
+                .packages
+                .iter()
+                .find(|p| p.name == name && p.source == source);
+            if let Some(package) = resolved {
+                if package.version != version {
+                    return Err(manifest_error(
+                        &path,
+                        format!(
+                            "`{}` is locked to version {}, but version {} was found at `{}`",
+                            name,
+                            version,
+                            package.version,
+                            package.root.display()
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the lockfile next to the root manifest, unless the one there
+    /// already lists the same packages.
+    pub fn write_lockfile(&self) -> Result<(), ShiroError> {
+        let path = self.root().root.join(LOCK_FILE);
+        let entries = self.lock_entries();
+        if let Some(locked) = read_lockfile(&path)? {
+            if locked.len() == entries.len() && entries.iter().all(|e| locked.contains(e)) {
+                return Ok(());
+            }
+        }
+        fs::write(&path, self.lockfile())
+            .map_err(|err| manifest_error(&path, format!("Cannot write lockfile: {}", err)))
+    }
+}
+
+/// Reads the package entries of the lockfile at `path`, if there is one.
+fn read_lockfile(path: &Path) -> Result<Option<Vec<Value>>, ShiroError> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let mut lock: Table = content
+        .parse()
+        .map_err(|err: toml::de::Error| manifest_error(path, err.message()))?;
+    match lock.remove("package") {
+        Some(Value::Array(entries)) => Ok(Some(entries)),
+        None => Ok(Some(Vec::new())),
+        Some(_) => Err(manifest_error(path, "`package` must be an array")),
+    }
+}
+
+fn package_dir(manifest_path: &Path) -> Result<PathBuf, ShiroError> {
+    let dir = manifest_path.parent().unwrap_or(Path::new("."));
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    fs::canonicalize(dir).map_err(|err| {
+        manifest_error(
+            manifest_path,
+            format!("Cannot open package directory: {}", err),
+        )
+    })
+}
+
+/// Expresses `path` relative to `base`, both of which must be canonical.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(path.components().skip(common));
+    relative
+}
@@ -33,6 +33,7 @@ impl CodeFile {
             }),
             Err(_) => Err(ShiroError::ModuleNotFound {
                 path: path.to_string(),
+                tried: vec![path.to_string()],
             }),
         }
     }
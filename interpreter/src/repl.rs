@@ -0,0 +1,99 @@
+use std::{env, path::PathBuf, process::ExitCode};
+
+use codespan_reporting::files::SimpleFiles;
+use rustyline::{error::ReadlineError, DefaultEditor};
+use shiro_interpreter::{
+    cli::{parse, Chunk},
+    CodeFile, Runtime, ShiroError, ShiroValue,
+};
+
+use crate::exit_code;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = ". ";
+const HISTORY_FILE: &str = ".shiro_history";
+
+/// Tells whether `code` is a complete piece of input, or would only parse
+/// once the user has typed more lines.
+fn is_incomplete(code: &str) -> bool {
+    matches!(try_parse(code), Err(ShiroError::UnrecognizedEOF { .. }))
+}
+
+fn try_parse(code: &str) -> Result<Chunk, ShiroError> {
+    parse(&mut SimpleFiles::new(), CodeFile::new("<repl>", code))
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Reads code from the terminal and evaluates it until the input ends.
+/// Everything runs in the same runtime, so definitions stay around.
+pub fn run(mut rt: Runtime) -> ExitCode {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("shiro: Cannot open terminal: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+            }
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("shiro: {}", err);
+                break;
+            }
+        }
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+
+        // A lone expression does not need its semicolon
+        let mut code = input.trim_end().to_string();
+        if is_incomplete(&code) {
+            code.push(';');
+            if try_parse(&code).is_err() {
+                continue;
+            }
+        }
+        let _ = editor.add_history_entry(input.trim_end());
+        input.clear();
+
+        match rt.eval_str("<repl>", &code) {
+            Ok(ShiroValue::Null) => {}
+            Ok(result) => println!("{}", result.coerce_string()),
+            Err(ShiroError::Exit { code }) => {
+                status = exit_code(code);
+                break;
+            }
+            Err(error) => rt.report_error(error),
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    status
+}
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::diag::ShiroError;
+
+use super::{
+    heap::Heap,
+    native::{Arity, NativeFunction},
+    userdata::UserData,
+    value::ShiroValue,
+};
+
+/// Conversion from a Shiro value into a Rust value. Unlike the `coerce_*`
+/// helpers, values of the wrong type are reported as an error.
+pub trait FromShiro: Sized {
+    /// Describes the accepted values in error messages.
+    fn expected() -> String;
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError>;
+}
+
+/// Conversion from a Rust value into a Shiro value, allocating on the heap
+/// where necessary.
+pub trait IntoShiro {
+    fn into_shiro(self, heap: &mut Heap) -> Result<ShiroValue, ShiroError>;
+}
+
+/// Names the type of a value for error messages, telling arrays and objects apart.
+pub fn describe_value(value: &ShiroValue, heap: &Heap) -> String {
+    match value {
+        ShiroValue::HeapRef(addr) => match heap.deref(*addr) {
+            Ok(obj) if obj.borrow().is_array() => "array".to_string(),
+            Ok(obj) => match obj.borrow().userdata() {
+                Some(data) => data.type_name().to_string(),
+                None => value.type_string(),
+            },
+            _ => value.type_string(),
+        },
+        _ => value.type_string(),
+    }
+}
+
+fn mismatch<T: FromShiro>(value: &ShiroValue, heap: &Heap) -> ShiroError {
+    ShiroError::TypeMismatch {
+        expected: T::expected(),
+        got: describe_value(value, heap),
+    }
+}
+
+impl FromShiro for ShiroValue {
+    fn expected() -> String {
+        "any value".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, _heap: &Heap) -> Result<Self, ShiroError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromShiro for UserData {
+    fn expected() -> String {
+        "host object".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        if let ShiroValue::HeapRef(addr) = value {
+            if let Some(data) = heap.deref(*addr)?.borrow().userdata() {
+                return Ok(data.clone());
+            }
+        }
+        Err(mismatch::<Self>(value, heap))
+    }
+}
+
+impl FromShiro for i64 {
+    fn expected() -> String {
+        "integer".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::Integer(i) => Ok(*i),
+            _ => Err(mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl FromShiro for f64 {
+    fn expected() -> String {
+        "decimal".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::Decimal(d) => Ok(*d),
+            ShiroValue::Integer(i) => Ok(*i as f64),
+            _ => Err(mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl FromShiro for String {
+    fn expected() -> String {
+        "string".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::String(str) => Ok(str.clone()),
+            ShiroValue::Char(c) => Ok(c.to_string()),
+            _ => Err(mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl FromShiro for char {
+    fn expected() -> String {
+        "char".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::Char(c) => Ok(*c),
+            _ => Err(mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl FromShiro for bool {
+    fn expected() -> String {
+        "boolean".to_string()
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::Boolean(b) => Ok(*b),
+            _ => Err(mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl<T: FromShiro> FromShiro for Option<T> {
+    fn expected() -> String {
+        format!("{} or null", T::expected())
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        match value {
+            ShiroValue::Null => Ok(None),
+            _ => T::from_shiro(value, heap)
+                .map(Some)
+                .map_err(|_| mismatch::<Self>(value, heap)),
+        }
+    }
+}
+
+impl<T: FromShiro> FromShiro for Vec<T> {
+    fn expected() -> String {
+        format!("array of {}", T::expected())
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        if let ShiroValue::HeapRef(addr) = value {
+            let obj = heap.deref(*addr)?;
+            let obj = obj.borrow();
+            if obj.is_array() {
+                return obj
+                    .values()
+                    .iter()
+                    .map(|item| T::from_shiro(item, heap))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| mismatch::<Self>(value, heap));
+            }
+        }
+        Err(mismatch::<Self>(value, heap))
+    }
+}
+
+impl<T: FromShiro> FromShiro for HashMap<String, T> {
+    fn expected() -> String {
+        format!("object of {}", T::expected())
+    }
+
+    fn from_shiro(value: &ShiroValue, heap: &Heap) -> Result<Self, ShiroError> {
+        if let ShiroValue::HeapRef(addr) = value {
+            let obj = heap.deref(*addr)?;
+            let obj = obj.borrow();
+            if !obj.is_array() {
+                return obj
+                    .entries()?
+                    .into_iter()
+                    .map(|(key, val)| Ok((key, T::from_shiro(&val, heap)?)))
+                    .collect::<Result<_, ShiroError>>()
+                    .map_err(|_| mismatch::<Self>(value, heap));
+            }
+        }
+        Err(mismatch::<Self>(value, heap))
+    }
+}
+
+impl IntoShiro for ShiroValue {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(self)
+    }
+}
+
+impl IntoShiro for () {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Null)
+    }
+}
+
+impl IntoShiro for i64 {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Integer(self))
+    }
+}
+
+impl IntoShiro for f64 {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Decimal(self))
+    }
+}
+
+impl IntoShiro for String {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::String(self))
+    }
+}
+
+impl IntoShiro for UserData {
+    fn into_shiro(self, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        heap.alloc_userdata(self)
+    }
+}
+
+impl IntoShiro for &str {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::String(self.to_string()))
+    }
+}
+
+impl IntoShiro for char {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Char(self))
+    }
+}
+
+impl IntoShiro for bool {
+    fn into_shiro(self, _heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Boolean(self))
+    }
+}
+
+impl<T: IntoShiro> IntoShiro for Option<T> {
+    fn into_shiro(self, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        match self {
+            Some(val) => val.into_shiro(heap),
+            None => Ok(ShiroValue::Null),
+        }
+    }
+}
+
+impl<T: IntoShiro> IntoShiro for Vec<T> {
+    fn into_shiro(self, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        let arr = heap.alloc_array()?;
+        for item in self {
+            let item = item.into_shiro(heap)?;
+            arr.borrow_mut().try_push(item)?;
+        }
+        let addr = arr.borrow().address();
+        Ok(ShiroValue::HeapRef(addr))
+    }
+}
+
+impl<K, T> IntoShiro for HashMap<K, T>
+where
+    K: Into<String> + Ord + Hash,
+    T: IntoShiro,
+{
+    fn into_shiro(self, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+        // Sort the keys so that the resulting object has a stable order
+        let mut entries: Vec<(K, T)> = self.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let obj = heap.alloc_object()?;
+        for (key, val) in entries {
+            let val = val.into_shiro(heap)?;
+            obj.borrow_mut().try_insert(&key.into(), val)?;
+        }
+        let addr = obj.borrow().address();
+        Ok(ShiroValue::HeapRef(addr))
+    }
+}
+
+/// A Rust function with typed arguments that can be turned into a native
+/// function. Arguments are converted with [`FromShiro`] and the result
+/// with [`IntoShiro`].
+pub trait IntoNativeFunction<Args> {
+    fn into_native_function(self, name: &str) -> NativeFunction;
+}
+
+fn convert_arg<T: FromShiro>(
+    function: &str,
+    position: usize,
+    value: &ShiroValue,
+    heap: &Heap,
+) -> Result<T, ShiroError> {
+    T::from_shiro(value, heap).map_err(|err| match err {
+        ShiroError::TypeMismatch { expected, got } => ShiroError::ArgumentType {
+            function: function.to_string(),
+            position: position + 1,
+            expected,
+            got,
+        },
+        err => err,
+    })
+}
+
+macro_rules! impl_into_native_function {
+    ($count:expr; $($arg:ident),*) => {
+        impl<Fun, Ret, Err, $($arg),*> IntoNativeFunction<($($arg,)*)> for Fun
+        where
+            Fun: Fn($($arg),*) -> Result<Ret, Err> + Send + Sync + 'static,
+            Ret: IntoShiro,
+            Err: Into<ShiroError>,
+            $($arg: FromShiro),*
+        {
+            #[allow(unused_variables, unused_mut)]
+            fn into_native_function(self, name: &str) -> NativeFunction {
+                let fn_name = name.to_string();
+                NativeFunction::new(name, Arity::Exact($count), move |args, ctx| {
+                    let mut args = args.iter().enumerate();
+                    let result = self($({
+                        let (position, value) = args.next().unwrap();
+                        convert_arg::<$arg>(&fn_name, position, value, &ctx.heap)?
+                    }),*);
+                    result.map_err(Into::into)?.into_shiro(&mut ctx.heap)
+                })
+            }
+        }
+    };
+}
+
+impl_into_native_function!(0;);
+impl_into_native_function!(1; A);
+impl_into_native_function!(2; A, B);
+impl_into_native_function!(3; A, B, C);
+impl_into_native_function!(4; A, B, C, D);
+impl_into_native_function!(5; A, B, C, D, E);
+impl_into_native_function!(6; A, B, C, D, E, F);
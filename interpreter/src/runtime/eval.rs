@@ -1,33 +1,74 @@
-use std::{cmp::min, rc::Rc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
 
 use crate::{
     ast::{AssignOpcode, BinaryOpcode, Expr, Reference, UnaryOpcode},
     diag::ShiroError,
+    package::{Package, PackageGraph},
     parser::CodeFile,
+    stdlib::string,
 };
 
-use super::{heap::Heap, scope::Scope, value::ShiroValue, Runtime};
+use super::{heap::Heap, native::Arity, scope::Scope, value::ShiroValue, Runtime};
+
+/// Once less than this much native stack is left, a function call
+/// continues on a freshly allocated stack segment.
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Reading the clock is comparatively slow, so the deadline is only
+/// checked every so many steps.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+fn undefined_variable(name: &ShiroValue) -> ShiroError {
+    ShiroError::UndefinedVariable {
+        name: name.coerce_string(),
+    }
+}
+
+/// The character at `index`, or null if it is out of range.
+fn char_at(str: &str, index: i64) -> ShiroValue {
+    match str.chars().nth(index as usize) {
+        Some(chr) => ShiroValue::Char(chr),
+        None => ShiroValue::Null,
+    }
+}
 
 fn get_value(
     name: &Vec<ShiroValue>,
-    scope: Rc<Scope>,
-    heap: &mut Heap,
+    scope: Arc<Scope>,
+    ctx: &mut Runtime,
 ) -> Result<ShiroValue, ShiroError> {
-    let mut val = scope.get_by_val(&name.first().expect("Invalid identifier"));
+    let local_name = name.first().expect("Invalid identifier");
+    let mut val = match scope.lookup(local_name.borrow_string()) {
+        Some(val) => val,
+        None if ctx.strict => return Err(undefined_variable(local_name)),
+        None => ShiroValue::Null,
+    };
 
     for p in name.iter().skip(1) {
         match &val {
             ShiroValue::HeapRef(addr) => {
-                let heap_obj = heap.deref(*addr);
+                let heap_obj = ctx.heap.deref(*addr)?;
                 val = heap_obj.borrow().get(p);
             }
-            ShiroValue::String(str) => {
-                let chr = str.chars().nth(p.coerce_integer() as usize);
-                match chr {
-                    Some(chr) => val = ShiroValue::Char(chr),
-                    None => val = ShiroValue::Null,
+            ShiroValue::String(str) => match p {
+                ShiroValue::String(name) => {
+                    val = match string::method(str, name) {
+                        Some(method) => ShiroValue::NativeFunction(method),
+                        // Numeric keys still index characters, e.g. `s['0']`
+                        None => match name.parse::<i64>() {
+                            Ok(index) => char_at(str, index),
+                            Err(_) => ShiroValue::Null,
+                        },
+                    };
                 }
-            }
+                _ => val = char_at(str, p.coerce_integer()),
+            },
             _ => {
                 return Err(ShiroError::GenericRuntimeError(format!(
                     "Cannot read property `{}` from a `{}` reference",
@@ -44,12 +85,14 @@ fn get_value(
 fn set_value(
     name: &Vec<ShiroValue>,
     new_val: ShiroValue,
-    scope: Rc<Scope>,
-    heap: &mut Heap,
+    scope: Arc<Scope>,
+    ctx: &mut Runtime,
 ) -> Result<(), ShiroError> {
     let local_name = name.first().expect("Invalid identifier");
     if name.len() == 1 {
-        scope.put_by_val(local_name, new_val, false);
+        if !scope.put_by_val(local_name, new_val, false) && ctx.strict {
+            return Err(undefined_variable(local_name));
+        }
     } else {
         let mut val = scope.get_by_val(local_name);
         let mut obj = None;
@@ -57,7 +100,7 @@ fn set_value(
         for i in 1..name.len() {
             let p = &name[i];
             if let ShiroValue::HeapRef(addr) = val {
-                let heap_obj = heap.deref(addr);
+                let heap_obj = ctx.heap.deref(addr)?;
                 val = heap_obj.borrow().get(p);
                 obj = Some(heap_obj);
             } else {
@@ -78,7 +121,7 @@ fn set_value(
 
 fn ref_to_string(
     r: &Reference,
-    scope: Rc<Scope>,
+    scope: Arc<Scope>,
     ctx: &mut Runtime,
 ) -> Result<Vec<ShiroValue>, ShiroError> {
     match &r {
@@ -97,27 +140,27 @@ fn map_strings(vec: &Vec<String>) -> Vec<ShiroValue> {
 }
 
 fn load_library(path: &str, ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
-    if ctx.libs.is_native_lib(path) {
-        Ok(ctx.libs.load(&path, &mut &mut ctx.heap))
+    if ctx.libs.is_disabled(path) {
+        Err(ShiroError::ModuleDisabled {
+            path: path.to_string(),
+        })
+    } else if ctx.libs.is_native_lib(path) {
+        ctx.libs.load(&path, &mut &mut ctx.heap)
     } else {
-        let stdlib_path = std::env::var("SHIRO_LIB_PATH");
-        let full_path = if path.starts_with('@') && stdlib_path.is_ok() {
-            format!("{}/{}.shiro", &stdlib_path.unwrap(), &path)
-        } else {
-            format!("{}.shiro", &path)
-        };
-        let file = CodeFile::open(&full_path)?;
-        ctx.eval_file(file)
+        let full_path = ctx.resolve_module(path)?;
+        ctx.load_module(full_path)
     }
 }
 
 pub trait Eval {
-    fn eval(self, scope: Rc<Scope>, ctx: &mut Runtime) -> Result<ShiroValue, ShiroError>;
+    fn eval(self, scope: Arc<Scope>, ctx: &mut Runtime) -> Result<ShiroValue, ShiroError>;
 }
 
 impl Eval for &Expr {
-    fn eval(self, scope: Rc<Scope>, ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    fn eval(self, scope: Arc<Scope>, ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+        ctx.step()?;
         match self {
+            Expr::Null => Ok(ShiroValue::Null),
             Expr::Decimal(val) => Ok(ShiroValue::Decimal(*val)),
             Expr::Integer(val) => Ok(ShiroValue::Integer(*val)),
             Expr::Boolean(val) => Ok(ShiroValue::Boolean(*val)),
@@ -129,7 +172,7 @@ impl Eval for &Expr {
             }
             Expr::Reference(r) => {
                 let ref_str = &ref_to_string(r, scope.clone(), ctx)?;
-                get_value(ref_str, scope.clone(), &mut ctx.heap)
+                get_value(ref_str, scope.clone(), ctx)
             }
             Expr::Import(path, name) => {
                 let lib = load_library(path, ctx)?;
@@ -184,37 +227,37 @@ impl Eval for &Expr {
                 match op {
                     AssignOpcode::Eq => {
                         let new_val = rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, new_val.clone(), scope.clone(), &mut ctx.heap)?;
+                        set_value(ref_str, new_val.clone(), scope.clone(), ctx)?;
                         Ok(new_val)
                     }
                     AssignOpcode::Add => {
-                        let val = get_value(ref_str, scope.clone(), &mut ctx.heap)?
+                        let val = get_value(ref_str, scope.clone(), ctx)?
                             + rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, val.clone(), scope.clone(), &mut ctx.heap)?;
+                        set_value(ref_str, val.clone(), scope.clone(), ctx)?;
                         Ok(val)
                     }
                     AssignOpcode::Sub => {
-                        let val = get_value(ref_str, scope.clone(), &mut ctx.heap)?
+                        let val = get_value(ref_str, scope.clone(), ctx)?
                             - rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, val.clone(), scope, &mut ctx.heap)?;
+                        set_value(ref_str, val.clone(), scope, ctx)?;
                         Ok(val)
                     }
                     AssignOpcode::Mul => {
-                        let val = get_value(ref_str, scope.clone(), &mut ctx.heap)?
+                        let val = get_value(ref_str, scope.clone(), ctx)?
                             * rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, val.clone(), scope, &mut ctx.heap)?;
+                        set_value(ref_str, val.clone(), scope, ctx)?;
                         Ok(val)
                     }
                     AssignOpcode::Div => {
-                        let val = get_value(ref_str, scope.clone(), &mut ctx.heap)?
+                        let val = get_value(ref_str, scope.clone(), ctx)?
                             / rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, val.clone(), scope, &mut ctx.heap)?;
+                        set_value(ref_str, val.clone(), scope, ctx)?;
                         Ok(val)
                     }
                     AssignOpcode::Mod => {
-                        let val = get_value(ref_str, scope.clone(), &mut ctx.heap)?
+                        let val = get_value(ref_str, scope.clone(), ctx)?
                             % rhs.eval(scope.clone(), ctx)?;
-                        set_value(ref_str, val.clone(), scope, &mut ctx.heap)?;
+                        set_value(ref_str, val.clone(), scope, ctx)?;
                         Ok(val)
                     }
                 }
@@ -222,7 +265,7 @@ impl Eval for &Expr {
             Expr::FunctionDecl(name, args, body) => {
                 let shiro_fun = ShiroValue::Function {
                     args: args.clone(),
-                    body: body.clone(),
+                    body: Arc::new(body.clone()),
                     scope: scope.clone(),
                 };
                 Ok(match name {
@@ -234,72 +277,14 @@ impl Eval for &Expr {
                 })
             }
             Expr::Invocation(name, in_args) => {
-                let shiro_name = map_strings(name);
-                let target = get_value(&shiro_name, scope.clone(), &mut ctx.heap)?;
+                let (target, args) = prepare_call(name, in_args, scope.clone(), ctx)?;
                 match target {
-                    ShiroValue::Function {
-                        args,
-                        body,
-                        scope: fun_scope,
-                    } => {
-                        let new_scope = Scope::new(Some(fun_scope.clone()));
-                        let matching_arg_num = min(in_args.len(), args.len());
-                        for i in 0..matching_arg_num {
-                            let arg_key = &args[i];
-                            let arg_val = in_args[i].eval(scope.clone(), ctx)?;
-                            new_scope.put_by_str(arg_key, arg_val, true);
-                        }
-                        let rc = Rc::new(new_scope);
-                        eval_block(&body, rc, ctx)
-                    }
-                    ShiroValue::NativeFunction(body) => body(in_args, scope, ctx),
-                    _ => Err(ShiroError::GenericRuntimeError(format!(
-                        "Cannot call reference `{}` that is of type `{}`",
-                        name.join("."),
-                        target
-                    ))),
+                    ShiroValue::NativeFunction(fun) => fun.call(&args, ctx),
+                    _ => call_function(target, args, ctx),
                 }
             }
-            Expr::Return(expr) => expr.eval(scope, ctx),
-            Expr::For(init_expr, condition_expr, inc_expr, body) => {
-                let new_scope = Rc::new(Scope::new(Some(scope.clone())));
-                init_expr.eval(new_scope.clone(), ctx)?;
-                while condition_expr
-                    .eval(new_scope.clone(), ctx)?
-                    .coerce_boolean()
-                {
-                    eval_block(body, new_scope.clone(), ctx)?;
-                    inc_expr.eval(new_scope.clone(), ctx)?;
-                }
-                Ok(ShiroValue::Null)
-            }
-            Expr::While(condition_expr, body) => {
-                let new_scope = Rc::new(Scope::new(Some(scope.clone())));
-                while condition_expr
-                    .eval(new_scope.clone(), ctx)?
-                    .coerce_boolean()
-                {
-                    eval_block(body, new_scope.clone(), ctx)?;
-                }
-                Ok(ShiroValue::Null)
-            }
-            Expr::If(branches) => {
-                for branch in branches {
-                    let new_scope = Rc::new(Scope::new(Some(scope.clone())));
-                    match &branch.condition {
-                        Some(c) => {
-                            if c.eval(new_scope.clone(), ctx)?.coerce_boolean() {
-                                return eval_block(&branch.body, new_scope.clone(), ctx);
-                            }
-                        }
-                        None => return eval_block(&branch.body, new_scope.clone(), ctx),
-                    }
-                }
-
-                Ok(ShiroValue::Null)
-            }
             Expr::ObjectDef(body) => {
-                let obj = &mut ctx.heap.alloc_object();
+                let obj = &mut ctx.heap.alloc_object()?;
                 let mut obj = obj.borrow_mut();
                 for def in body {
                     if let Expr::ObjectEntry(k, v) = def.as_ref() {
@@ -312,7 +297,7 @@ impl Eval for &Expr {
                 Ok(ShiroValue::HeapRef(obj.address()))
             }
             Expr::ArrayDef(items) => {
-                let arr = &mut ctx.heap.alloc_array();
+                let arr = &mut ctx.heap.alloc_array()?;
                 let mut arr = arr.borrow_mut();
                 for itm in items {
                     let val = itm.eval(scope.clone(), ctx)?;
@@ -331,103 +316,571 @@ impl Eval for &Expr {
     }
 }
 
+/// How control leaves a block of statements.
+enum Flow {
+    /// The block ran to its end, yielding the value of its last statement.
+    Value(ShiroValue),
+    /// A `return` statement was hit.
+    Return(ShiroValue),
+    /// A `return` statement in tail position wants to call a function. The
+    /// call is made by the caller of the block so that it can reuse its frame.
+    TailCall(ShiroValue, Vec<ShiroValue>),
+}
+
+fn prepare_call(
+    name: &Vec<String>,
+    in_args: &[Box<Expr>],
+    scope: Arc<Scope>,
+    ctx: &mut Runtime,
+) -> Result<(ShiroValue, Vec<ShiroValue>), ShiroError> {
+    ctx.check_interrupt()?;
+    let shiro_name = map_strings(name);
+    let target = get_value(&shiro_name, scope.clone(), ctx)?;
+    match target {
+        ShiroValue::Function { .. } | ShiroValue::NativeFunction(_) => {
+            let mut args = Vec::with_capacity(in_args.len());
+            for arg in in_args {
+                args.push(arg.eval(scope.clone(), ctx)?);
+            }
+            Ok((target, args))
+        }
+        _ => Err(ShiroError::GenericRuntimeError(format!(
+            "Cannot call reference `{}` that is of type `{}`",
+            name.join("."),
+            target
+        ))),
+    }
+}
+
+/// Collects what a `for .. in` loop visits: the keys of an object in
+/// insertion order, the items of an array or the characters of a string.
+fn iter_items(iterable: ShiroValue, heap: &Heap) -> Result<Vec<ShiroValue>, ShiroError> {
+    match &iterable {
+        ShiroValue::HeapRef(addr) => {
+            let obj = heap.deref(*addr)?;
+            let obj = obj.borrow();
+            if obj.is_array() {
+                Ok(obj.values())
+            } else {
+                Ok(obj.keys()?.into_iter().map(ShiroValue::String).collect())
+            }
+        }
+        ShiroValue::String(str) => Ok(str.chars().map(ShiroValue::Char).collect()),
+        _ => Err(ShiroError::GenericRuntimeError(format!(
+            "Cannot iterate over a `{}` value",
+            iterable
+        ))),
+    }
+}
+
+fn exec_stmt(expr: &Expr, scope: Arc<Scope>, ctx: &mut Runtime) -> Result<Flow, ShiroError> {
+    match expr {
+        Expr::Return(expr) => match expr.as_ref() {
+            Expr::Invocation(name, in_args) => {
+                let (target, args) = prepare_call(name, in_args, scope, ctx)?;
+                match target {
+                    ShiroValue::NativeFunction(fun) => Ok(Flow::Return(fun.call(&args, ctx)?)),
+                    _ => Ok(Flow::TailCall(target, args)),
+                }
+            }
+            _ => Ok(Flow::Return(expr.eval(scope, ctx)?)),
+        },
+        Expr::For(init_expr, condition_expr, inc_expr, body) => {
+            let new_scope = Arc::new(Scope::new(Some(scope.clone())));
+            init_expr.eval(new_scope.clone(), ctx)?;
+            while condition_expr
+                .eval(new_scope.clone(), ctx)?
+                .coerce_boolean()
+            {
+                ctx.check_interrupt()?;
+                if let flow @ (Flow::Return(_) | Flow::TailCall(..)) =
+                    eval_block(body, new_scope.clone(), ctx)?
+                {
+                    return Ok(flow);
+                }
+                inc_expr.eval(new_scope.clone(), ctx)?;
+            }
+            Ok(Flow::Value(ShiroValue::Null))
+        }
+        Expr::ForIn(name, iterable, body) => {
+            let new_scope = Arc::new(Scope::new(Some(scope.clone())));
+            let items = iter_items(iterable.eval(scope.clone(), ctx)?, &ctx.heap)?;
+            for item in items {
+                new_scope.put_by_str(name, item, true);
+                ctx.check_interrupt()?;
+                if let flow @ (Flow::Return(_) | Flow::TailCall(..)) =
+                    eval_block(body, new_scope.clone(), ctx)?
+                {
+                    return Ok(flow);
+                }
+            }
+            Ok(Flow::Value(ShiroValue::Null))
+        }
+        Expr::While(condition_expr, body) => {
+            let new_scope = Arc::new(Scope::new(Some(scope.clone())));
+            while condition_expr
+                .eval(new_scope.clone(), ctx)?
+                .coerce_boolean()
+            {
+                ctx.check_interrupt()?;
+                if let flow @ (Flow::Return(_) | Flow::TailCall(..)) =
+                    eval_block(body, new_scope.clone(), ctx)?
+                {
+                    return Ok(flow);
+                }
+            }
+            Ok(Flow::Value(ShiroValue::Null))
+        }
+        Expr::If(branches) => {
+            for branch in branches {
+                let new_scope = Arc::new(Scope::new(Some(scope.clone())));
+                match &branch.condition {
+                    Some(c) => {
+                        if c.eval(new_scope.clone(), ctx)?.coerce_boolean() {
+                            return eval_block(&branch.body, new_scope.clone(), ctx);
+                        }
+                    }
+                    None => return eval_block(&branch.body, new_scope.clone(), ctx),
+                }
+            }
+
+            Ok(Flow::Value(ShiroValue::Null))
+        }
+        _ => Ok(Flow::Value(expr.eval(scope, ctx)?)),
+    }
+}
+
 fn eval_block(
-    block: &Vec<Box<Expr>>,
-    scope: Rc<Scope>,
+    block: &[Box<Expr>],
+    scope: Arc<Scope>,
     ctx: &mut Runtime,
-) -> Result<ShiroValue, ShiroError> {
+) -> Result<Flow, ShiroError> {
     let mut retval = ShiroValue::Null;
     for expr in block {
-        let expr = expr.as_ref();
-        retval = expr.eval(scope.clone(), ctx)?;
-        if matches!(expr, Expr::Return(_)) {
-            break;
+        match exec_stmt(expr.as_ref(), scope.clone(), ctx)? {
+            Flow::Value(val) => retval = val,
+            flow => return Ok(flow),
         }
     }
-    Ok(retval)
+    Ok(Flow::Value(retval))
 }
 
-impl Runtime {
-    fn eval_tree(&mut self, tree: &Vec<Box<Expr>>) -> Result<ShiroValue, ShiroError> {
-        let global_scope = Rc::new(Scope::new(None));
-        global_scope.register_native_function("typeof", |args, scope, ctx| {
-            Ok(if args.len() == 0 {
-                ShiroValue::Null
-            } else {
-                let val = args.first().unwrap().eval(scope, ctx)?;
-                ShiroValue::String(val.type_string())
-            })
+/// Evaluates a block down to its final value, running a pending tail call.
+fn eval_block_value(
+    block: &[Box<Expr>],
+    scope: Arc<Scope>,
+    ctx: &mut Runtime,
+) -> Result<ShiroValue, ShiroError> {
+    match eval_block(block, scope, ctx)? {
+        Flow::Value(val) | Flow::Return(val) => Ok(val),
+        Flow::TailCall(target, args) => call_function(target, args, ctx),
+    }
+}
+
+fn call_function(
+    target: ShiroValue,
+    args: Vec<ShiroValue>,
+    ctx: &mut Runtime,
+) -> Result<ShiroValue, ShiroError> {
+    if ctx.call_depth >= ctx.max_call_depth {
+        return Err(ShiroError::StackOverflow {
+            depth: ctx.max_call_depth,
         });
-        global_scope.register_native_function("append", |args, scope, ctx| {
-            assert!(args.len() == 2);
-            let dst = args[0].eval(scope.clone(), ctx)?;
-            return if let ShiroValue::HeapRef(array_addr) = dst {
-                let array = &mut ctx.heap.deref(array_addr);
-                let mut array = array.borrow_mut();
-                let value = args[1].eval(scope.clone(), ctx)?;
-                array.try_push(value)?;
-                Ok(ShiroValue::Null)
-            } else {
-                Err(ShiroError::GenericRuntimeError(format!(
-                    "Cannot append to value of type {}",
-                    dst.type_string()
-                )))
+    }
+
+    ctx.call_depth += 1;
+    let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+        let mut target = target;
+        let mut args = args;
+        loop {
+            let ShiroValue::Function {
+                args: arg_names,
+                body,
+                scope: fun_scope,
+            } = target
+            else {
+                unreachable!("call_function invoked on a non-function value");
             };
-        });
-        global_scope.register_native_function("len", |args, scope, ctx| {
-            assert!(args.len() == 1);
-            let dst = args[0].eval(scope.clone(), ctx)?;
-            match &dst {
-                ShiroValue::HeapRef(array_addr) => {
-                    let obj = &mut ctx.heap.deref(*array_addr);
-                    let obj = obj.borrow_mut();
-                    Ok(ShiroValue::Integer(obj.len() as i64))
+
+            let new_scope = Scope::new(Some(fun_scope));
+            for (arg_key, arg_val) in arg_names.iter().zip(args) {
+                new_scope.put_by_str(arg_key, arg_val, true);
+            }
+
+            // A call in tail position replaces this frame instead of nesting a new one
+            match eval_block(&body, Arc::new(new_scope), ctx)? {
+                Flow::Value(val) | Flow::Return(val) => return Ok(val),
+                Flow::TailCall(next_target, next_args) => {
+                    target = next_target;
+                    args = next_args;
                 }
-                ShiroValue::String(str) => Ok(ShiroValue::Integer(str.len() as i64)),
-                _ => Err(ShiroError::GenericRuntimeError(format!(
-                    "Cannot retreive length of type {}",
-                    dst.type_string()
-                ))),
             }
-        });
-        global_scope.register_native_function("keys", |args, scope, ctx| {
-            assert!(args.len() == 1);
+        }
+    });
+    ctx.call_depth -= 1;
+    result
+}
+
+/// Creates the scope holding the functions that are available everywhere
+/// without an import.
+pub(super) fn builtins() -> Arc<Scope> {
+    let global_scope = Arc::new(Scope::new(None));
+    global_scope.register_native_function("typeof", Arity::Between(0, 1), |args, ctx| {
+        Ok(match args.first() {
+            Some(ShiroValue::HeapRef(addr)) => match ctx.heap.deref(*addr)?.borrow().userdata() {
+                Some(data) => ShiroValue::String(data.type_name().to_string()),
+                None => ShiroValue::String("object".to_string()),
+            },
+            Some(val) => ShiroValue::String(val.type_string()),
+            None => ShiroValue::Null,
+        })
+    });
+    global_scope.register_native_function("append", Arity::Exact(2), |args, ctx| {
+        let dst = &args[0];
+        return if let ShiroValue::HeapRef(array_addr) = dst {
+            let array = &mut ctx.heap.deref(*array_addr)?;
+            let mut array = array.borrow_mut();
+            array.try_push(args[1].clone())?;
+            Ok(ShiroValue::Null)
+        } else {
+            Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot append to value of type {}",
+                dst.type_string()
+            )))
+        };
+    });
+    global_scope.register_native_function("len", Arity::Exact(1), |args, ctx| {
+        let dst = &args[0];
+        match dst {
+            ShiroValue::HeapRef(array_addr) => {
+                let obj = &mut ctx.heap.deref(*array_addr)?;
+                let obj = obj.borrow_mut();
+                Ok(ShiroValue::Integer(obj.len() as i64))
+            }
+            ShiroValue::String(str) => Ok(ShiroValue::Integer(str.len() as i64)),
+            _ => Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot retreive length of type {}",
+                dst.type_string()
+            ))),
+        }
+    });
+    global_scope.register_native_function("keys", Arity::Exact(1), |args, ctx| {
+        let dst = &args[0];
+        if let ShiroValue::HeapRef(array_addr) = dst {
+            let obj = &mut ctx.heap.deref(*array_addr)?;
+            let obj = obj.borrow();
 
-            let dst = args[0].eval(scope.clone(), ctx)?;
-            if let ShiroValue::HeapRef(array_addr) = dst {
-                let obj = &mut ctx.heap.deref(array_addr);
-                let obj = obj.borrow();
+            let result_arr = &mut ctx.heap.alloc_array()?;
+            let mut result_arr = result_arr.borrow_mut();
 
-                let result_arr = &mut ctx.heap.alloc_array();
-                let mut result_arr = result_arr.borrow_mut();
+            for i in obj.keys()? {
+                result_arr.try_push(ShiroValue::String(i))?;
+            }
 
-                for i in obj.keys()? {
-                    result_arr.try_push(ShiroValue::String(i))?;
-                }
+            return Ok(ShiroValue::HeapRef(result_arr.address()));
+        } else {
+            Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot get keys of value of type {}",
+                dst.type_string()
+            )))
+        }
+    });
+    global_scope.register_native_function("values", Arity::Exact(1), |args, ctx| {
+        let dst = &args[0];
+        if let ShiroValue::HeapRef(obj_addr) = dst {
+            let values = ctx.heap.deref(*obj_addr)?.borrow().values();
 
-                return Ok(ShiroValue::HeapRef(result_arr.address()));
-            } else {
-                panic!("Cannot get length of value of type {}", dst.type_string());
+            let result_arr = &mut ctx.heap.alloc_array()?;
+            let mut result_arr = result_arr.borrow_mut();
+
+            for val in values {
+                result_arr.try_push(val)?;
             }
-        });
-        global_scope.register_native_function("dbg", |args, scope, ctx| {
-            for arg in args {
-                println!("[dbg] {:?} = {:?}", arg, arg.eval(scope.clone(), ctx)?);
+
+            Ok(ShiroValue::HeapRef(result_arr.address()))
+        } else {
+            Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot get values of value of type {}",
+                dst.type_string()
+            )))
+        }
+    });
+    global_scope.register_native_function("entries", Arity::Exact(1), |args, ctx| {
+        let dst = &args[0];
+        if let ShiroValue::HeapRef(obj_addr) = dst {
+            let entries = ctx.heap.deref(*obj_addr)?.borrow().entries()?;
+
+            let result_arr = &mut ctx.heap.alloc_array()?;
+            let mut result_arr = result_arr.borrow_mut();
+
+            for (key, val) in entries {
+                let entry = ctx.heap.alloc_array()?;
+                let mut entry = entry.borrow_mut();
+                entry.try_push(ShiroValue::String(key))?;
+                entry.try_push(val)?;
+                result_arr.try_push(ShiroValue::HeapRef(entry.address()))?;
             }
+
+            Ok(ShiroValue::HeapRef(result_arr.address()))
+        } else {
+            Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot get entries of value of type {}",
+                dst.type_string()
+            )))
+        }
+    });
+    global_scope.register_native_function("assert", Arity::Between(1, 2), |args, _| {
+        if args[0].coerce_boolean() {
             Ok(ShiroValue::Null)
-        });
+        } else {
+            Err(ShiroError::AssertionFailed {
+                message: match args.get(1) {
+                    Some(message) => message.coerce_string(),
+                    None => "condition is false".to_string(),
+                },
+            })
+        }
+    });
+    // Arguments arrive evaluated, so only their values can be shown and not
+    // the expressions that produced them
+    global_scope.register_native_function("dbg", Arity::Any, |args, _| {
+        for arg in args {
+            println!("[dbg] {:?}", arg);
+        }
+        Ok(ShiroValue::Null)
+    });
+    global_scope
+}
+
+impl Runtime {
+    fn step(&mut self) -> Result<(), ShiroError> {
+        self.steps += 1;
+        if let Some(max_steps) = self.max_steps {
+            if self.steps > max_steps {
+                return Err(ShiroError::StepLimitExceeded { limit: max_steps });
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(ShiroError::Timeout {
+                    limit: self.timeout.unwrap_or_default(),
+                });
+            }
+        }
+        Ok(())
+    }
 
-        eval_block(tree, global_scope, self)
+    /// Fails once the interrupt handle has been triggered, resetting it so
+    /// that the runtime can be used again afterwards.
+    fn check_interrupt(&self) -> Result<(), ShiroError> {
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            Err(ShiroError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn eval_tree(
+        &mut self,
+        tree: &[Box<Expr>],
+        scope: Arc<Scope>,
+    ) -> Result<ShiroValue, ShiroError> {
+        eval_block_value(tree, scope, self)
     }
 
-    fn eval_file(&mut self, file: CodeFile) -> Result<ShiroValue, ShiroError> {
+    /// Evaluates a module in a fresh scope of its own.
+    fn eval_module(&mut self, file: CodeFile) -> Result<ShiroValue, ShiroError> {
         let ast = self.parse_file(file)?;
-        self.eval_tree(&ast)
+        let scope = Arc::new(Scope::new(Some(self.builtins.clone())));
+        self.eval_tree(&ast, scope)
+    }
+
+    /// Finds the package named by the first segment of an import path among
+    /// the dependencies of the importing package, and the rest of the path.
+    fn resolve_dependency<'a>(&self, path: &'a str) -> Option<(&Package, Option<&'a str>)> {
+        let packages = self.packages.as_ref()?;
+        let importer = match self.loading.last() {
+            Some(file) => packages.package_of(file)?,
+            None => packages.root(),
+        };
+        let (name, module) = match path.split_once('/') {
+            Some((name, module)) => (name, Some(module)),
+            None => (path, None),
+        };
+        Some((packages.dependency(importer, name)?, module))
+    }
+
+    /// Finds the file of a module. `@`-prefixed modules are looked up in the
+    /// library search path, `pkgname/module` in the dependencies of the
+    /// importing package and all others relative to the importing file.
+    fn resolve_module(&self, path: &str) -> Result<PathBuf, ShiroError> {
+        let file_name = format!("{}.shiro", path);
+        let candidates: Vec<PathBuf> = if path.starts_with('@') {
+            self.lib_paths
+                .iter()
+                .map(|dir| dir.join(&file_name))
+                .collect()
+        } else if let Some((dep, module)) = self.resolve_dependency(path) {
+            match module {
+                Some(module) => vec![dep.root.join(format!("{}.shiro", module))],
+                None => vec![dep.entry.clone()],
+            }
+        } else {
+            let base_dir = match self.loading.last().and_then(|p| p.parent()) {
+                Some(dir) => dir.to_path_buf(),
+                None => PathBuf::new(),
+            };
+            vec![base_dir.join(&file_name)]
+        };
+
+        match candidates.iter().find(|candidate| candidate.is_file()) {
+            Some(found) => Ok(found.clone()),
+            None => Err(ShiroError::ModuleNotFound {
+                path: path.to_string(),
+                tried: candidates
+                    .iter()
+                    .map(|candidate| candidate.display().to_string())
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Evaluates the module at `path` unless it has been evaluated before,
+    /// returning its cached value in that case.
+    fn load_module(&mut self, path: PathBuf) -> Result<ShiroValue, ShiroError> {
+        let canonical_path = fs::canonicalize(&path).map_err(|_| ShiroError::ModuleNotFound {
+            path: path.display().to_string(),
+            tried: vec![path.display().to_string()],
+        })?;
+        if let Some(module) = self.modules.get(&canonical_path) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|p| *p == canonical_path) {
+            let chain = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&canonical_path))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(ShiroError::ImportCycle { chain });
+        }
+
+        let file = CodeFile::open(&canonical_path.to_string_lossy())?;
+        self.loading.push(canonical_path.clone());
+        let result = self.eval_module(file);
+        self.loading.pop();
+
+        let module = result?;
+        self.modules.insert(canonical_path, module.clone());
+        Ok(module)
     }
 
+    /// Evaluates a code file in the global scope. Globals defined by the
+    /// file stay around for later evaluations. Objects in the result are
+    /// collected when the next evaluation ends, unless stored in a global
+    /// or kept with [`Runtime::root`].
     pub fn eval(&mut self, code_file: CodeFile) -> Result<ShiroValue, ShiroError> {
-        let result = self.eval_file(code_file)?;
-        self.heap.gc();
-        Ok(result)
+        let ast = self.parse_file(code_file)?;
+        let nested = self.running;
+        let result = self.top_level(|rt| rt.eval_tree(&ast, rt.globals.clone()));
+        // Values held by a native function that evaluates more code are not
+        // known to the collector, so only collect once the outermost call ends
+        if !nested {
+            self.collect_garbage(&result);
+        }
+        result
+    }
+
+    /// Runs a top-level evaluation with a fresh step budget and deadline.
+    /// Calls made from within a running evaluation share its budget.
+    fn top_level<T>(
+        &mut self,
+        run: impl FnOnce(&mut Runtime) -> Result<T, ShiroError>,
+    ) -> Result<T, ShiroError> {
+        if self.running {
+            return run(self);
+        }
+        self.steps = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.running = true;
+        let result = run(self);
+        self.running = false;
+        result
+    }
+
+    /// Parses a code file without evaluating it, to report syntax errors.
+    pub fn check(&mut self, code_file: CodeFile) -> Result<(), ShiroError> {
+        self.parse_file(code_file).map(|_| ())
+    }
+
+    /// Evaluates a piece of code, using `name` to refer to it in diagnostics.
+    pub fn eval_str(&mut self, name: &str, code: &str) -> Result<ShiroValue, ShiroError> {
+        self.eval(CodeFile::new(name, code))
+    }
+
+    /// Reads and evaluates the code file at `path`.
+    pub fn eval_path(&mut self, path: &str) -> Result<ShiroValue, ShiroError> {
+        let file = CodeFile::open(path)?;
+        // Lets an import of the entry file be reported as a cycle
+        let canonical_path = fs::canonicalize(path).ok();
+        self.loading.extend(canonical_path.clone());
+        let result = self.eval(file);
+        if canonical_path.is_some() {
+            self.loading.pop();
+        }
+        result
+    }
+
+    /// Resolves the dependencies of the package described by the manifest at
+    /// `path`, so that the package's code can import them. The versions are
+    /// checked against the lockfile, which is then updated with any packages
+    /// that were added or removed.
+    pub fn load_manifest(&mut self, path: &Path) -> Result<(), ShiroError> {
+        let packages = PackageGraph::resolve(path)?;
+        packages.verify_lockfile()?;
+        packages.write_lockfile()?;
+        self.packages = Some(packages);
+        Ok(())
+    }
+
+    /// Loads the manifest at `path` and evaluates the package's entry file.
+    pub fn eval_package(&mut self, path: &Path) -> Result<ShiroValue, ShiroError> {
+        self.load_manifest(path)?;
+        let entry = self.packages.as_ref().unwrap().root().entry.clone();
+        self.eval_path(&entry.to_string_lossy())
+    }
+
+    /// Calls a function value with the given arguments. Like `eval`, a call
+    /// from the host starts with a fresh step budget and deadline.
+    pub fn call(
+        &mut self,
+        function: &ShiroValue,
+        args: Vec<ShiroValue>,
+    ) -> Result<ShiroValue, ShiroError> {
+        self.check_interrupt()?;
+        self.top_level(|rt| match function {
+            ShiroValue::Function { .. } => call_function(function.clone(), args, rt),
+            ShiroValue::NativeFunction(fun) => fun.call(&args, rt),
+            _ => Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot call value of type `{}`",
+                function
+            ))),
+        })
+    }
+
+    /// Calls the global function called `name` with the given arguments.
+    pub fn call_global(
+        &mut self,
+        name: &str,
+        args: Vec<ShiroValue>,
+    ) -> Result<ShiroValue, ShiroError> {
+        let function = self.get_global(name);
+        self.call(&function, args)
+    }
+
+    fn collect_garbage(&mut self, result: &Result<ShiroValue, ShiroError>) {
+        let mut roots: Vec<ShiroValue> = result.iter().cloned().collect();
+        roots.extend(self.modules.values().cloned());
+        roots.extend(self.roots.iter().cloned());
+        self.heap.gc(&roots, std::slice::from_ref(&self.globals));
     }
 }
@@ -1,15 +1,34 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use indexmap::IndexMap;
 
 use crate::diag::ShiroError;
 
-use super::{native::NativeFunctionPtr, value::ShiroValue};
+use super::{
+    convert::IntoNativeFunction,
+    lock_cell::LockCell,
+    native::{Arity, NativeFunction},
+    scope::Scope,
+    userdata::UserData,
+    value::ShiroValue,
+    Runtime,
+};
 
 const HEAP_DEBUG: bool = false;
 
 #[derive(Debug)]
 pub enum HeapValue {
-    Object(HashMap<String, ShiroValue>),
+    Object(IndexMap<String, ShiroValue>),
     Array(Vec<ShiroValue>),
+    UserData(UserData),
 }
 
 impl Display for HeapValue {
@@ -17,31 +36,95 @@ impl Display for HeapValue {
         match &self {
             HeapValue::Object(_) => f.write_str("object"),
             HeapValue::Array(_) => f.write_str("array"),
+            HeapValue::UserData(data) => f.write_str(data.type_name()),
+        }
+    }
+}
+
+/// Approximate number of bytes held by the heap, shared with all of its
+/// objects so that growing an object can be checked against the limit.
+#[derive(Debug, Default)]
+struct HeapUsage {
+    bytes: AtomicUsize,
+    max_bytes: LockCell<Option<usize>>,
+}
+
+/// The share of the heap usage that belongs to a single object.
+#[derive(Debug)]
+struct Footprint {
+    size: usize,
+    usage: Arc<HeapUsage>,
+}
+
+impl Footprint {
+    fn resize(&mut self, old_size: usize, new_size: usize) -> Result<(), ShiroError> {
+        if new_size >= old_size {
+            let bytes = self.usage.bytes.load(Ordering::Relaxed) + (new_size - old_size);
+            if let Some(max_bytes) = *self.usage.max_bytes.borrow() {
+                if bytes > max_bytes {
+                    return Err(ShiroError::HeapLimitExceeded {
+                        limit: max_bytes,
+                        unit: "bytes",
+                    });
+                }
+            }
+            self.usage.bytes.store(bytes, Ordering::Relaxed);
+        } else {
+            self.usage
+                .bytes
+                .fetch_sub(old_size - new_size, Ordering::Relaxed);
         }
+        self.size = self.size + new_size - old_size;
+        Ok(())
+    }
+}
+
+impl Drop for Footprint {
+    fn drop(&mut self) {
+        self.usage.bytes.fetch_sub(self.size, Ordering::Relaxed);
     }
 }
 
+fn value_size(val: &ShiroValue) -> usize {
+    size_of::<ShiroValue>()
+        + match val {
+            ShiroValue::String(str) => str.len(),
+            _ => 0,
+        }
+}
+
+fn entry_size(key: &str, val: &ShiroValue) -> usize {
+    size_of::<String>() + key.len() + value_size(val)
+}
+
 #[derive(Debug)]
 pub struct HeapObject {
-    address: u32,
+    address: HeapAddr,
     value: HeapValue,
+    footprint: Footprint,
 }
 
 impl HeapObject {
-    pub fn address(&self) -> u32 {
+    pub fn address(&self) -> HeapAddr {
         return self.address;
     }
 
     pub fn put(&mut self, key: ShiroValue, val: ShiroValue) -> Result<(), ShiroError> {
         match &mut self.value {
             HeapValue::Object(map) => {
-                map.insert(key.coerce_string(), val);
+                let key = key.coerce_string();
+                let old_size = map.get(&key).map_or(0, |old| entry_size(&key, old));
+                self.footprint.resize(old_size, entry_size(&key, &val))?;
+                map.insert(key, val);
             }
             HeapValue::Array(vec) => {
                 let idx = key.coerce_integer() as usize;
                 if idx < vec.len() {
+                    self.footprint
+                        .resize(value_size(&vec[idx]), value_size(&val))?;
                     vec[idx] = val;
                 } else if idx == vec.len() {
+                    self.footprint.resize(0, value_size(&val))?;
                     vec.push(val);
                 } else {
                     return Err(ShiroError::GenericRuntimeError(format!(
@@ -50,21 +133,60 @@ impl HeapObject {
                     )));
                 }
             }
+            HeapValue::UserData(data) => {
+                return Err(ShiroError::GenericRuntimeError(format!(
+                    "Cannot write property `{}` to a `{}`",
+                    key.coerce_string(),
+                    data.type_name()
+                )));
+            }
         }
         Ok(())
     }
 
-    pub fn must_insert_fun(&mut self, key: &str, fun: NativeFunctionPtr) {
-        self.try_insert_fun(key, fun)
+    pub fn must_insert_fun<F>(&mut self, key: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.try_insert_fun(key, arity, fun)
             .expect("Failed to register native function")
     }
 
-    pub fn try_insert_fun(&mut self, key: &str, fun: NativeFunctionPtr) -> Result<(), ShiroError> {
+    pub fn try_insert_fun<F>(&mut self, key: &str, arity: Arity, fun: F) -> Result<(), ShiroError>
+    where
+        F: Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let fun = NativeFunction::new(key, arity, fun);
+        self.try_insert(key, ShiroValue::NativeFunction(fun))
+    }
+
+    pub fn must_insert_typed_fun<Args, F>(&mut self, key: &str, fun: F)
+    where
+        F: IntoNativeFunction<Args>,
+    {
+        self.try_insert_typed_fun(key, fun)
+            .expect("Failed to register native function")
+    }
+
+    /// Inserts a Rust function with typed arguments, see [`IntoNativeFunction`].
+    pub fn try_insert_typed_fun<Args, F>(&mut self, key: &str, fun: F) -> Result<(), ShiroError>
+    where
+        F: IntoNativeFunction<Args>,
+    {
+        let fun = fun.into_native_function(key);
         self.try_insert(key, ShiroValue::NativeFunction(fun))
     }
 
     pub fn try_insert(&mut self, key: &str, val: ShiroValue) -> Result<(), ShiroError> {
         if let HeapValue::Object(map) = &mut self.value {
+            let old_size = map.get(key).map_or(0, |old| entry_size(key, old));
+            self.footprint.resize(old_size, entry_size(key, &val))?;
             map.insert(key.to_string(), val);
             Ok(())
         } else {
@@ -77,6 +199,7 @@ impl HeapObject {
 
     pub fn try_push(&mut self, val: ShiroValue) -> Result<(), ShiroError> {
         if let HeapValue::Array(vec) = &mut self.value {
+            self.footprint.resize(0, value_size(&val))?;
             vec.push(val);
             Ok(())
         } else {
@@ -87,10 +210,22 @@ impl HeapObject {
         }
     }
 
+    pub fn is_array(&self) -> bool {
+        matches!(self.value, HeapValue::Array(_))
+    }
+
+    pub fn userdata(&self) -> Option<&UserData> {
+        match &self.value {
+            HeapValue::UserData(data) => Some(data),
+            _ => None,
+        }
+    }
+
     pub fn len(&self) -> usize {
         match &self.value {
             HeapValue::Array(vec) => vec.len(),
             HeapValue::Object(map) => map.len(),
+            HeapValue::UserData(_) => 0,
         }
     }
 
@@ -100,6 +235,34 @@ impl HeapObject {
                 "Cannot get keys of an array".to_string(),
             )),
             HeapValue::Object(map) => Ok(map.keys().map(|k| k.to_string()).collect()),
+            HeapValue::UserData(data) => Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot get keys of a `{}`",
+                data.type_name()
+            ))),
+        }
+    }
+
+    pub fn values(&self) -> Vec<ShiroValue> {
+        match &self.value {
+            HeapValue::Array(vec) => vec.clone(),
+            HeapValue::Object(map) => map.values().cloned().collect(),
+            HeapValue::UserData(_) => Vec::new(),
+        }
+    }
+
+    pub fn entries(&self) -> Result<Vec<(String, ShiroValue)>, ShiroError> {
+        match &self.value {
+            HeapValue::Array(_) => Err(ShiroError::GenericRuntimeError(
+                "Cannot get entries of an array".to_string(),
+            )),
+            HeapValue::Object(map) => Ok(map
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect()),
+            HeapValue::UserData(data) => Err(ShiroError::GenericRuntimeError(format!(
+                "Cannot get entries of a `{}`",
+                data.type_name()
+            ))),
         }
     }
 
@@ -117,61 +280,217 @@ impl HeapObject {
                     ShiroValue::Null
                 }
             }
+            HeapValue::UserData(data) => match data.method(&key.coerce_string()) {
+                Some(fun) => ShiroValue::NativeFunction(fun),
+                None => ShiroValue::Null,
+            },
         }
     }
 }
 
+/// Identifies an object on the heap. The generation tells apart the
+/// different objects that occupy the same slot over time, so that a stale
+/// address cannot silently refer to a newer object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeapAddr {
+    index: u32,
+    generation: u32,
+}
+
+impl Display for HeapAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    object: Option<Arc<LockCell<HeapObject>>>,
+}
+
 #[derive(Debug)]
 pub struct Heap {
-    objects: HashMap<u32, Rc<RefCell<HeapObject>>>,
-    addr_ctr: u32,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    live_objects: usize,
+    max_objects: Option<usize>,
+    usage: Arc<HeapUsage>,
 }
 
 impl Heap {
     pub fn new() -> Heap {
         Heap {
-            objects: HashMap::new(),
-            addr_ctr: 1,
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            live_objects: 0,
+            max_objects: None,
+            usage: Arc::new(HeapUsage::default()),
         }
     }
 
-    pub fn alloc_object(&mut self) -> Rc<RefCell<HeapObject>> {
-        self.alloc_heap_value(HeapValue::Object(HashMap::new()))
+    /// Limits how many objects may be on the heap at once. Garbage is only
+    /// collected once a top-level evaluation ends, so objects that became
+    /// unreachable during it still count towards the limit until then.
+    pub fn set_max_objects(&mut self, max_objects: Option<usize>) {
+        self.max_objects = max_objects;
     }
 
-    pub fn alloc_array(&mut self) -> Rc<RefCell<HeapObject>> {
+    /// Limits the approximate number of bytes held by all heap objects,
+    /// which includes garbage in the same way as [`Heap::set_max_objects`].
+    pub fn set_max_bytes(&mut self, max_bytes: Option<usize>) {
+        *self.usage.max_bytes.borrow_mut() = max_bytes;
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.live_objects
+    }
+
+    pub fn byte_count(&self) -> usize {
+        self.usage.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn alloc_object(&mut self) -> Result<Arc<LockCell<HeapObject>>, ShiroError> {
+        self.alloc_heap_value(HeapValue::Object(IndexMap::new()))
+    }
+
+    pub fn alloc_array(&mut self) -> Result<Arc<LockCell<HeapObject>>, ShiroError> {
         self.alloc_heap_value(HeapValue::Array(Vec::new()))
     }
 
-    pub fn deref(&self, address: u32) -> Rc<RefCell<HeapObject>> {
-        self.objects[&address].clone()
+    /// Moves a host object onto the heap.
+    pub fn alloc_userdata(&mut self, data: UserData) -> Result<ShiroValue, ShiroError> {
+        let obj = self.alloc_heap_value(HeapValue::UserData(data))?;
+        let addr = obj.borrow().address();
+        Ok(ShiroValue::HeapRef(addr))
+    }
+
+    pub fn deref(&self, address: HeapAddr) -> Result<Arc<LockCell<HeapObject>>, ShiroError> {
+        match self.slots.get(address.index as usize) {
+            Some(Slot {
+                generation,
+                object: Some(obj),
+            }) if *generation == address.generation => Ok(obj.clone()),
+            _ => Err(ShiroError::DanglingReference {
+                address: address.to_string(),
+            }),
+        }
     }
 
-    pub fn gc(&mut self) {
+    /// Frees every object that can no longer be reached from the given
+    /// values and scopes. Objects that are currently borrowed by native
+    /// code are kept as well.
+    pub fn gc(&mut self, roots: &[ShiroValue], root_scopes: &[Arc<Scope>]) {
         if HEAP_DEBUG {
             println!("[gc] running cycle");
             dbg!(&self);
         }
-        self.objects.retain(|addr, obj| {
-            let ct = Rc::strong_count(&obj);
-            if HEAP_DEBUG {
-                println!("[gc] #{} has {} references", addr, ct);
+
+        let mut marked = vec![false; self.slots.len()];
+        let mut visited_scopes = HashSet::new();
+        let mut pending: Vec<ShiroValue> = roots.to_vec();
+        let mut pending_scopes: Vec<Arc<Scope>> = root_scopes.to_vec();
+
+        while !pending.is_empty() || !pending_scopes.is_empty() {
+            while let Some(scope) = pending_scopes.pop() {
+                if visited_scopes.insert(Arc::as_ptr(&scope)) {
+                    pending.extend(scope.values());
+                    if let Some(parent) = scope.parent() {
+                        pending_scopes.push(parent.clone());
+                    }
+                }
             }
-            ct > 1
-        });
-        // TODO cycle detection
+
+            while let Some(val) = pending.pop() {
+                match val {
+                    ShiroValue::HeapRef(addr) => {
+                        if let Ok(obj) = self.deref(addr) {
+                            let index = addr.index as usize;
+                            if !marked[index] {
+                                marked[index] = true;
+                                pending.extend(obj.borrow().values());
+                            }
+                        }
+                    }
+                    ShiroValue::Function { scope, .. } => pending_scopes.push(scope),
+                    _ => {}
+                }
+            }
+        }
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let collect = match &slot.object {
+                Some(obj) => {
+                    let ct = Arc::strong_count(obj);
+                    if HEAP_DEBUG {
+                        println!(
+                            "[gc] #{} has {} references, marked: {}",
+                            index, ct, marked[index]
+                        );
+                    }
+                    !marked[index] && ct <= 1
+                }
+                None => false,
+            };
+            if collect {
+                slot.object = None;
+                self.live_objects -= 1;
+
+                // A slot whose generation would wrap around is retired for good
+                if slot.generation < u32::MAX {
+                    slot.generation += 1;
+                    self.free_slots.push(index as u32);
+                }
+            }
+        }
     }
 
-    fn new_addr(&mut self) -> u32 {
-        let addr = self.addr_ctr;
-        self.addr_ctr += 1;
-        return addr;
+    fn new_addr(&mut self) -> HeapAddr {
+        match self.free_slots.pop() {
+            Some(index) => HeapAddr {
+                index,
+                generation: self.slots[index as usize].generation,
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    object: None,
+                });
+                HeapAddr {
+                    index: (self.slots.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
     }
 
-    fn alloc_heap_value(&mut self, value: HeapValue) -> Rc<RefCell<HeapObject>> {
+    fn alloc_heap_value(
+        &mut self,
+        value: HeapValue,
+    ) -> Result<Arc<LockCell<HeapObject>>, ShiroError> {
+        if let Some(max_objects) = self.max_objects {
+            if self.live_objects >= max_objects {
+                return Err(ShiroError::HeapLimitExceeded {
+                    limit: max_objects,
+                    unit: "objects",
+                });
+            }
+        }
+
+        let mut footprint = Footprint {
+            size: 0,
+            usage: self.usage.clone(),
+        };
+        footprint.resize(0, size_of::<HeapObject>())?;
+
         let address = self.new_addr();
-        let obj = Rc::new(RefCell::new(HeapObject { address, value }));
-        self.objects.insert(address, obj.clone());
-        obj
+        let obj = Arc::new(LockCell::new(HeapObject {
+            address,
+            value,
+            footprint,
+        }));
+        self.slots[address.index as usize].object = Some(obj.clone());
+        self.live_objects += 1;
+        Ok(obj)
     }
 }
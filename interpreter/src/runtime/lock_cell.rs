@@ -0,0 +1,33 @@
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+/// A `RefCell` that can be shared between threads. A runtime is only ever
+/// used by one thread at a time, so a borrow that conflicts with another
+/// one is a bug, just like with `RefCell`, and panics instead of blocking.
+#[derive(Debug, Default)]
+pub struct LockCell<T> {
+    lock: RwLock<T>,
+}
+
+impl<T> LockCell<T> {
+    pub fn new(value: T) -> LockCell<T> {
+        LockCell {
+            lock: RwLock::new(value),
+        }
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        match self.lock.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("already mutably borrowed"),
+        }
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+        match self.lock.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("already borrowed"),
+        }
+    }
+}
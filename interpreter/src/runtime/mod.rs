@@ -1,3 +1,14 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use codespan_reporting::{
     files::SimpleFiles,
     term::{
@@ -9,20 +20,84 @@ use codespan_reporting::{
 
 use crate::{
     diag::ShiroError,
+    package::PackageGraph,
     parser::{parse, Chunk, CodeFile},
 };
 
-use self::{heap::Heap, native::NativeLibProvider};
+use self::{
+    convert::IntoNativeFunction,
+    heap::Heap,
+    native::{Arity, NativeLibProvider},
+    scope::Scope,
+    value::ShiroValue,
+};
 
+pub mod convert;
 pub mod eval;
 pub mod heap;
-mod native;
+pub mod lock_cell;
+pub mod native;
 pub mod scope;
+pub mod serialize;
+pub mod userdata;
 pub mod value;
 
+const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Stops the evaluation of a runtime from any thread. The evaluation fails
+/// with [`ShiroError::Interrupted`] at its next loop iteration or call. An
+/// interrupt sent while nothing runs stops the next evaluation instead.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
+
 pub struct Runtime {
     pub heap: Heap,
     pub libs: NativeLibProvider,
+    pub max_call_depth: usize,
+    call_depth: usize,
+    /// Evaluation steps allowed per top-level `eval` or `call`.
+    pub max_steps: Option<u64>,
+    steps: u64,
+    /// Wall-clock time allowed per top-level `eval` or `call`. It is checked
+    /// between interpreter steps, so time spent blocked in a native
+    /// function is only noticed once it returns.
+    pub timeout: Option<Duration>,
+    /// Makes reading or assigning an undefined variable an error instead
+    /// of yielding `null` or doing nothing.
+    pub strict: bool,
+    /// Lets scripts change the environment variables and working directory
+    /// through `@std/os`. These belong to the whole process rather than to
+    /// the runtime, and changing environment variables while another thread
+    /// reads them is unsound on some platforms, so this is off by default.
+    /// Only enable it when no other thread uses the process environment.
+    pub allow_process_changes: bool,
+    /// Command line arguments passed to the script.
+    pub args: Vec<String>,
+    /// Directories searched for `@`-prefixed modules, in order.
+    pub lib_paths: Vec<PathBuf>,
+    /// Dependencies that `import 'pkgname/module'` is resolved against.
+    pub packages: Option<PackageGraph>,
+    deadline: Option<Instant>,
+    /// Set while a top-level `eval` or `call` runs, so that host calls made
+    /// from native functions share its step budget and deadline.
+    running: bool,
+    interrupted: Arc<AtomicBool>,
+    builtins: Arc<Scope>,
+    globals: Arc<Scope>,
+    /// Export values of the modules evaluated so far, by canonical path.
+    modules: HashMap<PathBuf, ShiroValue>,
+    /// Modules that are currently being evaluated, in import order.
+    loading: Vec<PathBuf>,
+    /// Values held by the host that the garbage collector must keep.
+    roots: Vec<ShiroValue>,
     files: SimpleFiles<String, String>,
     diag_stream: StandardStream,
     diag_config: Config,
@@ -30,15 +105,109 @@ pub struct Runtime {
 
 impl Runtime {
     pub fn new() -> Self {
+        let builtins = eval::builtins();
+        let globals = Arc::new(Scope::new(Some(builtins.clone())));
         Runtime {
             heap: Heap::new(),
             libs: NativeLibProvider::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            call_depth: 0,
+            max_steps: None,
+            steps: 0,
+            timeout: None,
+            strict: false,
+            allow_process_changes: false,
+            args: Vec::new(),
+            lib_paths: default_lib_paths(),
+            packages: None,
+            deadline: None,
+            running: false,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            builtins,
+            globals,
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            roots: Vec::new(),
             files: SimpleFiles::new(),
             diag_stream: StandardStream::stderr(ColorChoice::Auto),
             diag_config: codespan_reporting::term::Config::default(),
         }
     }
 
+    /// Returns a handle that other threads can use to stop a running
+    /// evaluation.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: self.interrupted.clone(),
+        }
+    }
+
+    /// Looks up a variable in the global scope, which is shared by all
+    /// code evaluated through this runtime.
+    pub fn get_global(&self, name: &str) -> ShiroValue {
+        self.globals.get_by_str(name)
+    }
+
+    /// Names of all variables defined in the global scope.
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals.names()
+    }
+
+    pub fn set_global(&mut self, name: &str, value: ShiroValue) {
+        self.globals.put_by_str(name, value, true);
+    }
+
+    /// Keeps `value`, and everything it refers to, from being collected.
+    ///
+    /// The garbage collector runs after each top-level evaluation and only
+    /// knows about globals, module exports and the result. Any other object
+    /// the host holds on to becomes a dangling reference once the next
+    /// evaluation ends, unless it is rooted. Every call needs a matching
+    /// [`Runtime::unroot`].
+    pub fn root(&mut self, value: &ShiroValue) {
+        if matches!(value, ShiroValue::HeapRef(_) | ShiroValue::Function { .. }) {
+            self.roots.push(value.clone());
+        }
+    }
+
+    /// Releases a value rooted with [`Runtime::root`], so that it is
+    /// collected once nothing else refers to it.
+    pub fn unroot(&mut self, value: &ShiroValue) {
+        let position = self.roots.iter().position(|root| match (root, value) {
+            (ShiroValue::HeapRef(a), ShiroValue::HeapRef(b)) => a == b,
+            (ShiroValue::Function { scope: a, .. }, ShiroValue::Function { scope: b, .. }) => {
+                Arc::ptr_eq(a, b)
+            }
+            _ => false,
+        });
+        if let Some(position) = position {
+            self.roots.swap_remove(position);
+        }
+    }
+
+    /// Defines a global function backed by a Rust closure, which may
+    /// capture state from the host.
+    pub fn register_native_function<F>(&mut self, name: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.globals.register_native_function(name, arity, fun);
+    }
+
+    /// Defines a global function backed by a Rust function with typed
+    /// arguments, see [`IntoNativeFunction`].
+    pub fn register_typed_function<Args, F>(&mut self, name: &str, fun: F)
+    where
+        F: IntoNativeFunction<Args>,
+    {
+        let fun = fun.into_native_function(name);
+        self.globals
+            .put_by_str(name, ShiroValue::NativeFunction(fun), true);
+    }
+
     pub fn report_error(&self, error: ShiroError) {
         term::emit(
             &mut self.diag_stream.lock(),
@@ -53,3 +222,31 @@ impl Runtime {
         parse(&mut self.files, file)
     }
 }
+
+/// The directories listed in `SHIRO_LIB_PATH`, followed by the `libs`
+/// directory next to the executable.
+pub fn default_lib_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(var) = env::var_os("SHIRO_LIB_PATH") {
+        paths.extend(env::split_paths(&var).filter(|p| !p.as_os_str().is_empty()));
+    }
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+    {
+        paths.push(exe_dir.join("libs"));
+    }
+    paths
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A runtime may be moved to another thread as a whole
+const _: () = {
+    fn assert_send<T: Send>() {}
+    let _ = assert_send::<Runtime>;
+};
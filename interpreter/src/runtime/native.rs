@@ -1,55 +1,169 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::Arc,
+};
 
-use crate::{ast::Expr, diag::ShiroError, stdlib};
+use crate::{diag::ShiroError, stdlib};
 
 use super::{
     heap::{Heap, HeapObject},
-    scope::Scope,
     value::ShiroValue,
     Runtime,
 };
 
-pub type NativeFunctionPtr = fn(
-    args: &Vec<Box<Expr>>,
-    scope: Rc<Scope>,
-    ctx: &mut Runtime,
-) -> Result<ShiroValue, ShiroError>;
+/// How many arguments a native function accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+    Any,
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(min) => count >= min,
+            Arity::Between(min, max) => count >= min && count <= max,
+            Arity::Any => true,
+        }
+    }
+
+    /// The arity of the same function when it takes `n` more arguments
+    /// up front, like a method called as a plain function.
+    pub fn with_leading(self, n: usize) -> Arity {
+        match self {
+            Arity::Exact(count) => Arity::Exact(count + n),
+            Arity::AtLeast(min) => Arity::AtLeast(min + n),
+            Arity::Between(min, max) => Arity::Between(min + n, max + n),
+            Arity::Any => Arity::AtLeast(n),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Arity::Exact(1) => write!(f, "1 argument"),
+            Arity::Exact(n) => write!(f, "{} arguments", n),
+            Arity::AtLeast(1) => write!(f, "at least 1 argument"),
+            Arity::AtLeast(min) => write!(f, "at least {} arguments", min),
+            Arity::Between(min, max) => write!(f, "{} to {} arguments", min, max),
+            Arity::Any => write!(f, "any number of arguments"),
+        }
+    }
+}
+
+pub type NativeFunctionBody =
+    dyn Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError> + Send + Sync;
+
+/// A function implemented in Rust. It receives its arguments already
+/// evaluated and may capture state from the host.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: Arc<str>,
+    arity: Arity,
+    body: Arc<NativeFunctionBody>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: Arity, body: F) -> Self
+    where
+        F: Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        NativeFunction {
+            name: name.into(),
+            arity,
+            body: Arc::new(body),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    pub fn call(&self, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+        if !self.arity.accepts(args.len()) {
+            return Err(ShiroError::ArityMismatch {
+                function: self.name.to_string(),
+                expected: self.arity.to_string(),
+                got: args.len(),
+            });
+        }
+        (self.body)(args, ctx)
+    }
+}
 
 pub type NativeLibCreator = fn(obj: &mut HeapObject);
 
 pub struct NativeLibProvider {
     registry: HashMap<String, NativeLibCreator>,
+    disabled: HashSet<String>,
 }
 
 impl NativeLibProvider {
     pub fn new() -> NativeLibProvider {
         NativeLibProvider {
             registry: HashMap::new(),
+            disabled: HashSet::new(),
         }
     }
     pub fn is_native_lib(&self, name: &str) -> bool {
         return self.registry.contains_key(name);
     }
-    pub fn load(&self, name: &str, heap: &mut Heap) -> ShiroValue {
+    pub fn is_disabled(&self, name: &str) -> bool {
+        return self.disabled.contains(name);
+    }
+    pub fn load(&self, name: &str, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
         let creator = self.registry[name];
 
-        let obj = heap.alloc_object();
+        let obj = heap.alloc_object()?;
         let mut obj = obj.borrow_mut();
 
         creator(&mut obj);
 
-        ShiroValue::HeapRef(obj.address())
+        Ok(ShiroValue::HeapRef(obj.address()))
     }
     pub fn register_lib(&mut self, name: &str, creator: NativeLibCreator) {
+        self.disabled.remove(name);
         self.registry.insert(name.to_string(), creator);
     }
+    /// Prevents scripts from importing the given library. To keep untrusted
+    /// code away from the host, disable every library that reaches it:
+    /// `@std/fs` (files), `@std/os` (environment, host information and
+    /// `exit`), `@std/process` (running programs), `@std/io` (the terminal)
+    /// and `@std/path` (which resolves against the working directory).
+    ///
+    /// Step and time limits only count interpreter steps. A native function
+    /// that blocks, such as `io.readline`, `process.spawn` or the `read_line`
+    /// and `wait` methods of a process, is not interrupted by the
+    /// [`timeout`](super::Runtime::timeout).
+    pub fn disable_lib(&mut self, name: &str) {
+        self.registry.remove(name);
+        self.disabled.insert(name.to_string());
+    }
 }
 
 impl Default for NativeLibProvider {
     fn default() -> Self {
         let mut provider = Self::new();
+        provider.register_lib("@std/fs", stdlib::fs);
         provider.register_lib("@std/io", stdlib::io);
+        provider.register_lib("@std/json", stdlib::json);
         provider.register_lib("@std/os", stdlib::os);
+        provider.register_lib("@std/path", stdlib::path);
+        provider.register_lib("@std/process", stdlib::process);
+        provider.register_lib("@std/regex", stdlib::regex);
+        provider.register_lib("@std/string", stdlib::string);
         provider.register_lib("@std/net", stdlib::net);
         provider.register_lib("@std/time", stdlib::time);
         provider
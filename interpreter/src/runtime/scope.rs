@@ -1,43 +1,71 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{collections::HashMap, sync::Arc};
 
-use super::{native::NativeFunctionPtr, value::ShiroValue};
+use crate::diag::ShiroError;
+
+use super::{
+    lock_cell::LockCell,
+    native::{Arity, NativeFunction},
+    value::ShiroValue,
+    Runtime,
+};
 
 #[derive(Debug)]
 pub struct Scope {
-    parent: Option<Rc<Scope>>,
-    vars: RefCell<HashMap<String, ShiroValue>>,
+    parent: Option<Arc<Scope>>,
+    vars: LockCell<HashMap<String, ShiroValue>>,
 }
 
 impl Scope {
-    pub fn new(parent: Option<Rc<Scope>>) -> Scope {
+    pub fn new(parent: Option<Arc<Scope>>) -> Scope {
         Scope {
             parent,
-            vars: RefCell::new(HashMap::new()),
+            vars: LockCell::new(HashMap::new()),
         }
     }
 
+    pub fn parent(&self) -> Option<&Arc<Scope>> {
+        self.parent.as_ref()
+    }
+
+    pub fn values(&self) -> Vec<ShiroValue> {
+        self.vars.borrow().values().cloned().collect()
+    }
+
     pub fn get_by_val(&self, name: &ShiroValue) -> ShiroValue {
         let name_str = name.borrow_string();
         return self.get_by_str(name_str);
     }
 
     pub fn get_by_str(&self, name: &str) -> ShiroValue {
+        self.lookup(name).unwrap_or(ShiroValue::Null)
+    }
+
+    /// Like [`Scope::get_by_str`], but tells an undefined variable apart
+    /// from one that holds `null`.
+    pub fn lookup(&self, name: &str) -> Option<ShiroValue> {
         if !self.vars.borrow().contains_key(name) {
             match &self.parent {
-                Some(parent) => parent.get_by_str(name),
-                _ => ShiroValue::Null,
+                Some(parent) => parent.lookup(name),
+                _ => None,
             }
         } else {
-            self.vars.borrow()[name].clone()
+            Some(self.vars.borrow()[name].clone())
         }
     }
 
+    /// Names of the variables defined directly in this scope.
+    pub fn names(&self) -> Vec<String> {
+        self.vars.borrow().keys().cloned().collect()
+    }
+
     pub fn put_by_str(&self, name: &str, val: ShiroValue, define: bool) {
         self.put_cascade(name.to_string(), val, define);
     }
 
-    pub fn put_by_val(&self, name: &ShiroValue, val: ShiroValue, define: bool) {
-        self.put_cascade(name.coerce_string(), val, define);
+    /// Returns whether the value was stored, which it is not when assigning
+    /// to a variable that has not been defined.
+    pub fn put_by_val(&self, name: &ShiroValue, val: ShiroValue, define: bool) -> bool {
+        self.put_cascade(name.coerce_string(), val, define)
     }
 
     fn put_cascade(&self, name: String, val: ShiroValue, define: bool) -> bool {
@@ -54,9 +82,16 @@ impl Scope {
         false
     }
 
-    pub fn register_native_function(&self, name: &str, ptr: NativeFunctionPtr) {
+    pub fn register_native_function<F>(&self, name: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let fun = NativeFunction::new(name, arity, fun);
         self.vars
             .borrow_mut()
-            .insert(name.to_string(), ShiroValue::NativeFunction(ptr));
+            .insert(name.to_string(), ShiroValue::NativeFunction(fun));
     }
 }
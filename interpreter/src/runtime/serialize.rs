@@ -0,0 +1,713 @@
+use std::cell::RefCell;
+
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Serialize},
+};
+
+use crate::diag::ShiroError;
+
+use super::{
+    heap::{Heap, HeapAddr},
+    value::ShiroValue,
+};
+
+/// Converts any serializable Rust value into a Shiro value. Structs and maps
+/// become objects and sequences become arrays, both allocated on `heap`.
+pub fn to_value<T: Serialize + ?Sized>(
+    value: &T,
+    heap: &mut Heap,
+) -> Result<ShiroValue, ShiroError> {
+    value.serialize(ValueSerializer { heap })
+}
+
+/// Converts a Shiro value, including the objects and arrays it refers to,
+/// into any deserializable Rust value. A cyclic structure cannot be
+/// converted and is reported as an error.
+pub fn from_value<T: DeserializeOwned>(value: &ShiroValue, heap: &Heap) -> Result<T, ShiroError> {
+    T::deserialize(ValueDeserializer {
+        value,
+        heap,
+        path: &RefCell::new(Vec::new()),
+    })
+}
+
+impl ser::Error for ShiroError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ShiroError::ConversionError(msg.to_string())
+    }
+}
+
+impl de::Error for ShiroError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ShiroError::ConversionError(msg.to_string())
+    }
+}
+
+fn alloc_array(items: Vec<ShiroValue>, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+    let arr = heap.alloc_array()?;
+    let mut arr = arr.borrow_mut();
+    for item in items {
+        arr.try_push(item)?;
+    }
+    Ok(ShiroValue::HeapRef(arr.address()))
+}
+
+fn alloc_object(
+    entries: Vec<(String, ShiroValue)>,
+    heap: &mut Heap,
+) -> Result<ShiroValue, ShiroError> {
+    let obj = heap.alloc_object()?;
+    let mut obj = obj.borrow_mut();
+    for (key, val) in entries {
+        obj.try_insert(&key, val)?;
+    }
+    Ok(ShiroValue::HeapRef(obj.address()))
+}
+
+/// Wraps the value of an enum variant in an object keyed by the variant name.
+fn alloc_variant(
+    variant: &str,
+    value: ShiroValue,
+    heap: &mut Heap,
+) -> Result<ShiroValue, ShiroError> {
+    alloc_object(vec![(variant.to_string(), value)], heap)
+}
+
+struct ValueSerializer<'a> {
+    heap: &'a mut Heap,
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = SeqSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ShiroValue, ShiroError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ShiroValue, ShiroError> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Err(ShiroError::ConversionError(format!(
+                "{} is too large for an integer",
+                v
+            ))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ShiroValue, ShiroError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Decimal(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<ShiroValue, ShiroError> {
+        let items = v.iter().map(|b| ShiroValue::Integer(*b as i64)).collect();
+        alloc_array(items, self.heap)
+    }
+
+    fn serialize_none(self) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<ShiroValue, ShiroError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<ShiroValue, ShiroError> {
+        Ok(ShiroValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ShiroValue, ShiroError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<ShiroValue, ShiroError> {
+        let value = value.serialize(ValueSerializer {
+            heap: &mut *self.heap,
+        })?;
+        alloc_variant(variant, value, self.heap)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>, ShiroError> {
+        Ok(SeqSerializer {
+            heap: self.heap,
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, ShiroError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, ShiroError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, ShiroError> {
+        Ok(SeqSerializer {
+            heap: self.heap,
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer<'a>, ShiroError> {
+        Ok(MapSerializer {
+            heap: self.heap,
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a>, ShiroError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a>, ShiroError> {
+        Ok(MapSerializer {
+            heap: self.heap,
+            variant: Some(variant),
+            entries: Vec::with_capacity(len),
+            next_key: None,
+        })
+    }
+}
+
+struct SeqSerializer<'a> {
+    heap: &'a mut Heap,
+    variant: Option<&'static str>,
+    items: Vec<ShiroValue>,
+}
+
+impl SeqSerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        let value = value.serialize(ValueSerializer {
+            heap: &mut *self.heap,
+        })?;
+        self.items.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ShiroValue, ShiroError> {
+        let arr = alloc_array(self.items, self.heap)?;
+        match self.variant {
+            Some(variant) => alloc_variant(variant, arr, self.heap),
+            None => Ok(arr),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a> {
+    heap: &'a mut Heap,
+    variant: Option<&'static str>,
+    entries: Vec<(String, ShiroValue)>,
+    next_key: Option<String>,
+}
+
+impl MapSerializer<'_> {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), ShiroError> {
+        let value = value.serialize(ValueSerializer {
+            heap: &mut *self.heap,
+        })?;
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ShiroValue, ShiroError> {
+        let obj = alloc_object(self.entries, self.heap)?;
+        match self.variant {
+            Some(variant) => alloc_variant(variant, obj, self.heap),
+            None => Ok(obj),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ShiroError> {
+        let key = key.serialize(ValueSerializer {
+            heap: &mut *self.heap,
+        })?;
+        match key {
+            ShiroValue::String(_)
+            | ShiroValue::Char(_)
+            | ShiroValue::Integer(_)
+            | ShiroValue::Boolean(_) => {
+                self.next_key = Some(key.coerce_string());
+                Ok(())
+            }
+            _ => Err(ShiroError::ConversionError(format!(
+                "Object keys must be strings, but got {}",
+                key.type_string()
+            ))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ShiroError> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ShiroError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = ShiroValue;
+    type Error = ShiroError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ShiroError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<ShiroValue, ShiroError> {
+        self.finish()
+    }
+}
+
+/// Reads a Shiro value into Rust. `path` holds the objects currently being
+/// read, so that a cycle is reported instead of recursing forever.
+struct ValueDeserializer<'a> {
+    value: &'a ShiroValue,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl ValueDeserializer<'_> {
+    /// Marks `addr` as being read while `read` runs.
+    fn enter<T>(
+        &self,
+        addr: HeapAddr,
+        read: impl FnOnce() -> Result<T, ShiroError>,
+    ) -> Result<T, ShiroError> {
+        if self.path.borrow().contains(&addr) {
+            return Err(ShiroError::ConversionError(
+                "Cannot convert a cyclic structure".to_string(),
+            ));
+        }
+        self.path.borrow_mut().push(addr);
+        let result = read();
+        self.path.borrow_mut().pop();
+        result
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = ShiroError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ShiroError> {
+        match self.value {
+            ShiroValue::Null => visitor.visit_unit(),
+            ShiroValue::Boolean(b) => visitor.visit_bool(*b),
+            ShiroValue::Integer(i) => visitor.visit_i64(*i),
+            ShiroValue::Decimal(d) => visitor.visit_f64(*d),
+            ShiroValue::Char(c) => visitor.visit_char(*c),
+            ShiroValue::String(str) => visitor.visit_str(str),
+            ShiroValue::HeapRef(addr) => {
+                let obj = self.heap.deref(*addr)?;
+                let obj = obj.borrow();
+                self.enter(*addr, || {
+                    if obj.is_array() {
+                        visitor.visit_seq(SeqDeserializer {
+                            items: obj.values().into_iter(),
+                            heap: self.heap,
+                            path: self.path,
+                        })
+                    } else {
+                        visitor.visit_map(MapDeserializer {
+                            entries: obj.entries()?.into_iter(),
+                            value: None,
+                            heap: self.heap,
+                            path: self.path,
+                        })
+                    }
+                })
+            }
+            ShiroValue::Function { .. } | ShiroValue::NativeFunction(_) => Err(
+                ShiroError::ConversionError("Cannot convert a function".to_string()),
+            ),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ShiroError> {
+        match self.value {
+            ShiroValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ShiroError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ShiroError> {
+        match self.value {
+            ShiroValue::String(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            ShiroValue::HeapRef(addr) => {
+                let entries = self.heap.deref(*addr)?.borrow().entries()?;
+                match <[(String, ShiroValue); 1]>::try_from(entries) {
+                    Ok([(variant, value)]) => self.enter(*addr, || {
+                        visitor.visit_enum(EnumDeserializer {
+                            variant,
+                            value,
+                            heap: self.heap,
+                            path: self.path,
+                        })
+                    }),
+                    Err(_) => Err(ShiroError::ConversionError(
+                        "Expected an object with a single key for an enum variant".to_string(),
+                    )),
+                }
+            }
+            _ => Err(ShiroError::ConversionError(format!(
+                "Expected a string or object for an enum, but got {}",
+                self.value.type_string()
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<'a> {
+    items: std::vec::IntoIter<ShiroValue>,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_> {
+    type Error = ShiroError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ShiroError> {
+        match self.items.next() {
+            Some(item) => seed
+                .deserialize(ValueDeserializer {
+                    value: &item,
+                    heap: self.heap,
+                    path: self.path,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapDeserializer<'a> {
+    entries: std::vec::IntoIter<(String, ShiroValue)>,
+    value: Option<ShiroValue>,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer<'_> {
+    type Error = ShiroError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ShiroError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ShiroError> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer {
+            value: &value,
+            heap: self.heap,
+            path: self.path,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer<'a> {
+    variant: String,
+    value: ShiroValue,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = ShiroError;
+    type Variant = VariantDeserializer<'a>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer<'a>), ShiroError> {
+        let variant = seed.deserialize(IntoDeserializer::<ShiroError>::into_deserializer(
+            self.variant,
+        ))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                value: self.value,
+                heap: self.heap,
+                path: self.path,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer<'a> {
+    value: ShiroValue,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer<'_> {
+    type Error = ShiroError;
+
+    fn unit_variant(self) -> Result<(), ShiroError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ShiroError> {
+        seed.deserialize(ValueDeserializer {
+            value: &self.value,
+            heap: self.heap,
+            path: self.path,
+        })
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ShiroError> {
+        de::Deserializer::deserialize_any(
+            ValueDeserializer {
+                value: &self.value,
+                heap: self.heap,
+                path: self.path,
+            },
+            visitor,
+        )
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ShiroError> {
+        de::Deserializer::deserialize_any(
+            ValueDeserializer {
+                value: &self.value,
+                heap: self.heap,
+                path: self.path,
+            },
+            visitor,
+        )
+    }
+}
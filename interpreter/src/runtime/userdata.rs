@@ -0,0 +1,95 @@
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use crate::diag::ShiroError;
+
+use super::{
+    native::{Arity, NativeFunction},
+    value::ShiroValue,
+    Runtime,
+};
+
+pub type MethodBody =
+    dyn Fn(&UserData, &[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError> + Send + Sync;
+
+/// Describes a kind of host object: the name `typeof` reports for it and the
+/// methods scripts can call on it.
+pub struct UserDataType {
+    name: Arc<str>,
+    methods: HashMap<String, (Arity, Arc<MethodBody>)>,
+}
+
+impl UserDataType {
+    pub fn new(name: &str) -> UserDataType {
+        UserDataType {
+            name: name.into(),
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Adds a method. Its arity does not count the object it is called on.
+    pub fn method<F>(mut self, name: &str, arity: Arity, body: F) -> Self
+    where
+        F: Fn(&UserData, &[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.methods
+            .insert(name.to_string(), (arity, Arc::new(body)));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A Rust value owned by a script, e.g. a file handle or a socket. The value
+/// is dropped once the heap object holding it is collected and no bound
+/// method refers to it anymore, so `Drop` can release the resource.
+#[derive(Clone)]
+pub struct UserData {
+    ty: Arc<UserDataType>,
+    data: Arc<dyn Any + Send + Sync>,
+}
+
+impl std::fmt::Debug for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("UserData").field(&self.ty.name).finish()
+    }
+}
+
+impl UserData {
+    pub fn new<T: Any + Send + Sync>(ty: &Arc<UserDataType>, data: T) -> UserData {
+        UserData {
+            ty: ty.clone(),
+            data: Arc::new(data),
+        }
+    }
+
+    pub fn type_name(&self) -> &str {
+        self.ty.name()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
+    /// Like [`UserData::downcast_ref`], but reports a wrong type as an error.
+    pub fn downcast<T: Any>(&self) -> Result<&T, ShiroError> {
+        self.downcast_ref().ok_or_else(|| ShiroError::TypeMismatch {
+            expected: std::any::type_name::<T>().to_string(),
+            got: self.type_name().to_string(),
+        })
+    }
+
+    /// Looks up a method and binds it to this object.
+    pub fn method(&self, name: &str) -> Option<NativeFunction> {
+        let (arity, body) = self.ty.methods.get(name)?;
+        let this = self.clone();
+        let body = body.clone();
+        Some(NativeFunction::new(name, *arity, move |args, ctx| {
+            body(&this, args, ctx)
+        }))
+    }
+}
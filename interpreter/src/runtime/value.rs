@@ -1,13 +1,13 @@
 use std::{
     cmp::Ordering::{Equal, Greater, Less},
     ops::{Add, Div, Mul, Rem, Sub},
-    rc::Rc,
     str::FromStr,
+    sync::Arc,
 };
 
 use crate::ast::Expr;
 
-use super::{native::NativeFunctionPtr, scope::Scope};
+use super::{heap::HeapAddr, native::NativeFunction, scope::Scope};
 
 #[derive(Clone)]
 pub enum ShiroValue {
@@ -18,12 +18,12 @@ pub enum ShiroValue {
     Char(char),
     Function {
         args: Vec<String>,
-        body: Vec<Box<Expr>>,
-        scope: Rc<Scope>,
+        body: Arc<Vec<Box<Expr>>>,
+        scope: Arc<Scope>,
     },
-    NativeFunction(NativeFunctionPtr),
+    NativeFunction(NativeFunction),
     Null,
-    HeapRef(u32),
+    HeapRef(HeapAddr),
 }
 
 impl std::fmt::Display for ShiroValue {
@@ -55,7 +55,9 @@ impl std::fmt::Debug for ShiroValue {
                 .field("args", args)
                 .field("body", body)
                 .finish(),
-            Self::NativeFunction(_) => write!(f, "NativeFunction"),
+            Self::NativeFunction(fun) => {
+                f.debug_tuple("NativeFunction").field(&fun.name()).finish()
+            }
             Self::HeapRef(addr) => f.debug_tuple("HeapRef").field(addr).finish(),
             Self::Null => write!(f, "Null"),
         }
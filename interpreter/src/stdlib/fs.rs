@@ -0,0 +1,175 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::FromShiro,
+        heap::HeapObject,
+        native::Arity,
+        userdata::{UserData, UserDataType},
+        value::ShiroValue,
+    },
+};
+
+pub(super) fn io_error(path: &str, err: std::io::Error) -> ShiroError {
+    ShiroError::IoError {
+        path: path.to_string(),
+        message: err.to_string(),
+    }
+}
+
+/// Locks a native handle's state. A method that panicked while holding the
+/// lock leaves it poisoned, but the state itself is still usable.
+pub(super) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reads one line without its line ending, or `None` at the end of input.
+pub(super) fn read_line<R: BufRead>(reader: &mut R) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+/// Reads a file one line at a time. The file is closed at the end of the
+/// file, by `close()` or once the reader is collected.
+struct LineReader {
+    path: String,
+    reader: Mutex<Option<BufReader<File>>>,
+}
+
+fn line_reader_type() -> UserDataType {
+    UserDataType::new("LineReader")
+        .method("next", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<LineReader>()?;
+            let mut reader = lock(&this.reader);
+            let Some(file) = reader.as_mut() else {
+                return Ok(ShiroValue::Null);
+            };
+
+            match read_line(file) {
+                Ok(Some(line)) => Ok(ShiroValue::String(line)),
+                Ok(None) => {
+                    *reader = None;
+                    Ok(ShiroValue::Null)
+                }
+                Err(err) => Err(io_error(&this.path, err)),
+            }
+        })
+        .method("close", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<LineReader>()?;
+            *lock(&this.reader) = None;
+            Ok(ShiroValue::Null)
+        })
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    obj.must_insert_typed_fun("read_text", |path: String| {
+        fs::read_to_string(&path).map_err(|err| io_error(&path, err))
+    });
+    obj.must_insert_typed_fun("read_bytes", |path: String| {
+        fs::read(&path)
+            .map(|bytes| bytes.into_iter().map(i64::from).collect::<Vec<_>>())
+            .map_err(|err| io_error(&path, err))
+    });
+    obj.must_insert_typed_fun("write_text", |path: String, text: String| {
+        fs::write(&path, text).map_err(|err| io_error(&path, err))
+    });
+    obj.must_insert_typed_fun("append", |path: String, text: String| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|err| io_error(&path, err))
+    });
+    obj.must_insert_typed_fun("exists", |path: String| -> Result<bool, ShiroError> {
+        Ok(Path::new(&path).exists())
+    });
+    obj.must_insert_fun("stat", Arity::Exact(1), |args, ctx| {
+        let path = String::from_shiro(&args[0], &ctx.heap)?;
+        let meta = fs::symlink_metadata(&path).map_err(|err| io_error(&path, err))?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(ShiroValue::Null, |time| {
+                ShiroValue::Integer(time.as_millis() as i64)
+            });
+
+        let stat = ctx.heap.alloc_object()?;
+        let mut stat = stat.borrow_mut();
+        stat.try_insert("size", ShiroValue::Integer(meta.len() as i64))?;
+        stat.try_insert("is_file", ShiroValue::Boolean(meta.is_file()))?;
+        stat.try_insert("is_dir", ShiroValue::Boolean(meta.is_dir()))?;
+        stat.try_insert("is_symlink", ShiroValue::Boolean(meta.is_symlink()))?;
+        stat.try_insert(
+            "readonly",
+            ShiroValue::Boolean(meta.permissions().readonly()),
+        )?;
+        stat.try_insert("modified", modified)?;
+        Ok(ShiroValue::HeapRef(stat.address()))
+    });
+    obj.must_insert_typed_fun("list_dir", |path: String| {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&path).map_err(|err| io_error(&path, err))? {
+            let entry = entry.map_err(|err| io_error(&path, err))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        Ok::<_, ShiroError>(names)
+    });
+    obj.must_insert_typed_fun("mkdir_all", |path: String| {
+        fs::create_dir_all(&path).map_err(|err| io_error(&path, err))
+    });
+    obj.must_insert_fun("remove", Arity::Between(1, 2), |args, ctx| {
+        let path = String::from_shiro(&args[0], &ctx.heap)?;
+        let recursive = match args.get(1) {
+            Some(recursive) => bool::from_shiro(recursive, &ctx.heap)?,
+            None => false,
+        };
+        let meta = fs::symlink_metadata(&path).map_err(|err| io_error(&path, err))?;
+        let result = if !meta.is_dir() {
+            fs::remove_file(&path)
+        } else if recursive {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_dir(&path)
+        };
+        result.map_err(|err| io_error(&path, err))?;
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_typed_fun("rename", |from: String, to: String| {
+        fs::rename(&from, &to).map_err(|err| io_error(&from, err))
+    });
+    obj.must_insert_typed_fun("copy", |from: String, to: String| {
+        fs::copy(&from, &to)
+            .map(|_| ())
+            .map_err(|err| io_error(&from, err))
+    });
+
+    let line_reader = Arc::new(line_reader_type());
+    obj.must_insert_fun("lines", Arity::Exact(1), move |args, ctx| {
+        let path = String::from_shiro(&args[0], &ctx.heap)?;
+        let file = File::open(&path).map_err(|err| io_error(&path, err))?;
+        let reader = LineReader {
+            path,
+            reader: Mutex::new(Some(BufReader::new(file))),
+        };
+        ctx.heap.alloc_userdata(UserData::new(&line_reader, reader))
+    });
+}
@@ -1,34 +1,214 @@
-use std::rc::Rc;
+use std::io::{self, Read, Write};
 
 use crate::{
-    ast::Expr,
     diag::ShiroError,
-    runtime::{eval::Eval, heap::HeapObject, scope::Scope, value::ShiroValue, Runtime},
+    runtime::{
+        convert::FromShiro,
+        heap::{Heap, HeapObject},
+        native::Arity,
+        value::ShiroValue,
+    },
 };
 
-fn eval_contact(
-    vec: &Vec<Box<Expr>>,
-    scope: Rc<Scope>,
-    ctx: &mut Runtime,
-) -> Result<String, ShiroError> {
-    let mut str = String::new();
-    for arg in vec {
-        str.push_str(arg.eval(scope.clone(), ctx)?.coerce_string().as_str());
-        str.push(' ');
+use super::fs::{io_error, read_line};
+
+/// Joins the arguments with single spaces, like `print` does.
+fn concat(args: &[ShiroValue]) -> String {
+    args.iter()
+        .map(ShiroValue::coerce_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_error(message: &str) -> ShiroError {
+    ShiroError::GenericRuntimeError(format!("Invalid format string: {}", message))
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    zero: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+/// Parses the part of a placeholder after the colon:
+/// `[[fill]align][0][width][.precision]`.
+fn parse_spec(spec: &str) -> Result<Spec, ShiroError> {
+    let mut result = Spec::default();
+    let mut chars: Vec<char> = spec.chars().collect();
+    let is_align = |c: &char| matches!(c, '<' | '>' | '^');
+
+    if chars.len() >= 2 && is_align(&chars[1]) {
+        result.fill = Some(chars[0]);
+        result.align = Some(chars[1]);
+        chars.drain(..2);
+    } else if chars.first().is_some_and(is_align) {
+        result.align = Some(chars[0]);
+        chars.remove(0);
+    }
+    if chars.first() == Some(&'0') {
+        result.zero = true;
+        chars.remove(0);
     }
-    return Ok(str);
+
+    let rest: String = chars.into_iter().collect();
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (rest.as_str(), None),
+    };
+    let number = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| format_error(&format!("bad specifier `{{:{}}}`", spec)))
+    };
+    if !width.is_empty() {
+        result.width = Some(number(width)?);
+    }
+    if let Some(precision) = precision {
+        result.precision = Some(number(precision)?);
+    }
+    Ok(result)
+}
+
+fn format_value(value: &ShiroValue, spec: &Spec) -> String {
+    let numeric = matches!(value, ShiroValue::Integer(_) | ShiroValue::Decimal(_));
+    let text = match (value, spec.precision) {
+        (ShiroValue::Integer(_) | ShiroValue::Decimal(_), Some(precision)) => {
+            format!("{:.*}", precision, value.coerce_decimal())
+        }
+        (_, Some(precision)) => value.coerce_string().chars().take(precision).collect(),
+        _ => value.coerce_string(),
+    };
+
+    let width = spec.width.unwrap_or(0);
+    let len = text.chars().count();
+    if len >= width {
+        return text;
+    }
+    let padding = width - len;
+
+    if spec.zero && numeric && spec.align.is_none() {
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", text.as_str()),
+        };
+        return format!("{}{}{}", sign, "0".repeat(padding), digits);
+    }
+
+    let fill = spec.fill.unwrap_or(' ').to_string();
+    // Numbers line up on the right by default, everything else on the left
+    let align = spec.align.unwrap_or(if numeric { '>' } else { '<' });
+    let (left, right) = match align {
+        '>' => (padding, 0),
+        '^' => (padding / 2, padding - padding / 2),
+        _ => (0, padding),
+    };
+    format!("{}{}{}", fill.repeat(left), text, fill.repeat(right))
+}
+
+/// Formats `fmt` with `{}` placeholders, which take an optional spec after a
+/// colon (`{:>8}`, `{:.2}`, `{:08.3}`). `{{` and `}}` are literal braces.
+fn format(fmt: &str, args: &[ShiroValue]) -> Result<String, ShiroError> {
+    let mut result = String::new();
+    let mut args = args.iter();
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format_error("unclosed `{`")),
+                    }
+                }
+                let spec = match placeholder.strip_prefix(':') {
+                    Some(spec) => parse_spec(spec)?,
+                    None if placeholder.is_empty() => Spec::default(),
+                    None => {
+                        return Err(format_error(&format!(
+                            "unknown placeholder `{{{}}}`",
+                            placeholder
+                        )))
+                    }
+                };
+                let Some(arg) = args.next() else {
+                    return Err(format_error("more placeholders than arguments"));
+                };
+                result.push_str(&format_value(arg, &spec));
+            }
+            '}' => return Err(format_error("unmatched `}`")),
+            c => result.push(c),
+        }
+    }
+    if args.next().is_some() {
+        return Err(format_error("more arguments than placeholders"));
+    }
+    Ok(result)
+}
+
+fn format_args(args: &[ShiroValue], heap: &Heap) -> Result<String, ShiroError> {
+    let fmt = String::from_shiro(&args[0], heap)?;
+    format(&fmt, &args[1..])
 }
 
 pub fn lib(obj: &mut HeapObject) {
-    obj.must_insert_fun("hello_native", |_, _, _| {
-        Ok(ShiroValue::String("hello from the other side".to_string()))
+    obj.must_insert_fun("println", Arity::Any, |args, _| {
+        println!("{}", concat(args));
+        Ok(ShiroValue::Null)
     });
-    obj.must_insert_fun("println", |args, scope, ctx| {
-        println!("{}", eval_contact(args, scope, ctx)?);
+    obj.must_insert_fun("print", Arity::Any, |args, _| {
+        print!("{}", concat(args));
         Ok(ShiroValue::Null)
     });
-    obj.must_insert_fun("print", |args, scope, ctx| {
-        print!("{}", eval_contact(args, scope, ctx)?);
+    obj.must_insert_fun("eprintln", Arity::Any, |args, _| {
+        eprintln!("{}", concat(args));
         Ok(ShiroValue::Null)
     });
+    obj.must_insert_fun("eprint", Arity::Any, |args, _| {
+        eprint!("{}", concat(args));
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_fun("format", Arity::AtLeast(1), |args, ctx| {
+        Ok(ShiroValue::String(format_args(args, &ctx.heap)?))
+    });
+    obj.must_insert_fun("printf", Arity::AtLeast(1), |args, ctx| {
+        print!("{}", format_args(args, &ctx.heap)?);
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_typed_fun("flush", || -> Result<(), ShiroError> {
+        io::stdout()
+            .flush()
+            .map_err(|err| io_error("<stdout>", err))?;
+        io::stderr()
+            .flush()
+            .map_err(|err| io_error("<stderr>", err))
+    });
+    obj.must_insert_fun("readline", Arity::Exact(0), |_, _| {
+        // Show a pending prompt before blocking on input
+        let _ = io::stdout().flush();
+        match read_line(&mut io::stdin().lock()) {
+            Ok(Some(line)) => Ok(ShiroValue::String(line)),
+            Ok(None) => Ok(ShiroValue::Null),
+            Err(err) => Err(io_error("<stdin>", err)),
+        }
+    });
+    obj.must_insert_typed_fun("read_all", || -> Result<String, ShiroError> {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| io_error("<stdin>", err))?;
+        Ok(text)
+    });
 }
@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::{describe_value, FromShiro},
+        heap::{Heap, HeapAddr, HeapObject},
+        native::Arity,
+        value::ShiroValue,
+    },
+};
+
+fn parse_error(err: serde_json::Error) -> ShiroError {
+    // The position is reported separately, so drop it from the message
+    let message = err.to_string();
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    };
+    ShiroError::JsonError {
+        line: err.line(),
+        column: err.column(),
+        message,
+    }
+}
+
+fn from_json(value: Value, heap: &mut Heap) -> Result<ShiroValue, ShiroError> {
+    Ok(match value {
+        Value::Null => ShiroValue::Null,
+        Value::Bool(b) => ShiroValue::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => ShiroValue::Integer(i),
+            None => ShiroValue::Decimal(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => ShiroValue::String(s),
+        Value::Array(items) => {
+            let arr = heap.alloc_array()?;
+            for item in items {
+                let item = from_json(item, heap)?;
+                arr.borrow_mut().try_push(item)?;
+            }
+            let addr = arr.borrow().address();
+            ShiroValue::HeapRef(addr)
+        }
+        Value::Object(entries) => {
+            let obj = heap.alloc_object()?;
+            for (key, val) in entries {
+                let val = from_json(val, heap)?;
+                obj.borrow_mut().try_insert(&key, val)?;
+            }
+            let addr = obj.borrow().address();
+            ShiroValue::HeapRef(addr)
+        }
+    })
+}
+
+/// Serializes a value together with everything it refers to. As in the
+/// serde `ValueDeserializer`, `path` holds the objects currently being
+/// written, so that a cycle is reported instead of recursing forever, while
+/// an object shared by two fields is fine.
+struct Json<'a> {
+    value: &'a ShiroValue,
+    heap: &'a Heap,
+    path: &'a RefCell<Vec<HeapAddr>>,
+}
+
+impl Json<'_> {
+    fn child<'b>(&'b self, value: &'b ShiroValue) -> Json<'b> {
+        Json {
+            value,
+            heap: self.heap,
+            path: self.path,
+        }
+    }
+}
+
+impl Serialize for Json<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let addr = match self.value {
+            ShiroValue::Null => return serializer.serialize_unit(),
+            ShiroValue::Boolean(b) => return serializer.serialize_bool(*b),
+            ShiroValue::Integer(i) => return serializer.serialize_i64(*i),
+            ShiroValue::Decimal(d) => return serializer.serialize_f64(*d),
+            ShiroValue::String(s) => return serializer.serialize_str(s),
+            ShiroValue::Char(c) => return serializer.serialize_char(*c),
+            ShiroValue::HeapRef(addr) => *addr,
+            _ => {
+                return Err(ser::Error::custom(format!(
+                    "Cannot convert {} to JSON",
+                    describe_value(self.value, self.heap)
+                )))
+            }
+        };
+
+        if self.path.borrow().contains(&addr) {
+            return Err(ser::Error::custom(
+                "Cannot convert a cyclic structure to JSON",
+            ));
+        }
+        let obj = self.heap.deref(addr).map_err(ser::Error::custom)?;
+        let obj = obj.borrow();
+        if obj.userdata().is_some() {
+            return Err(ser::Error::custom(format!(
+                "Cannot convert {} to JSON",
+                describe_value(self.value, self.heap)
+            )));
+        }
+
+        self.path.borrow_mut().push(addr);
+        let result = if obj.is_array() {
+            let values = obj.values();
+            drop(obj);
+            let mut seq = serializer.serialize_seq(Some(values.len()))?;
+            for value in &values {
+                seq.serialize_element(&self.child(value))?;
+            }
+            seq.end()
+        } else {
+            let entries = obj.entries().map_err(ser::Error::custom)?;
+            drop(obj);
+            let mut map = serializer.serialize_map(Some(entries.len()))?;
+            for (key, value) in &entries {
+                map.serialize_entry(key, &self.child(value))?;
+            }
+            map.end()
+        };
+        self.path.borrow_mut().pop();
+        result
+    }
+}
+
+fn stringify(value: &ShiroValue, heap: &Heap, indent: usize) -> Result<String, ShiroError> {
+    let json = Json {
+        value,
+        heap,
+        path: &RefCell::new(Vec::new()),
+    };
+    let mut out = Vec::new();
+    let result = if indent == 0 {
+        json.serialize(&mut Serializer::new(&mut out))
+    } else {
+        let indent = " ".repeat(indent);
+        let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+        json.serialize(&mut Serializer::with_formatter(&mut out, formatter))
+    };
+    result.map_err(|err| ShiroError::ConversionError(err.to_string()))?;
+    Ok(String::from_utf8(out).expect("JSON output is valid UTF-8"))
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    obj.must_insert_fun("parse", Arity::Exact(1), |args, ctx| {
+        let text = String::from_shiro(&args[0], &ctx.heap)?;
+        let value = serde_json::from_str(&text).map_err(parse_error)?;
+        from_json(value, &mut ctx.heap)
+    });
+    // The optional second argument pretty-prints: `true` indents by two
+    // spaces, an integer by that many
+    obj.must_insert_fun("stringify", Arity::Between(1, 2), |args, ctx| {
+        let indent = match args.get(1) {
+            None | Some(ShiroValue::Boolean(false)) => 0,
+            Some(ShiroValue::Boolean(true)) => 2,
+            Some(indent) => i64::from_shiro(indent, &ctx.heap)?.max(0) as usize,
+        };
+        Ok(ShiroValue::String(stringify(&args[0], &ctx.heap, indent)?))
+    });
+}
@@ -1,9 +1,21 @@
+mod fs;
 mod io;
+mod json;
 mod net;
 mod os;
+mod path;
+mod process;
+mod regex;
+pub(crate) mod string;
 mod time;
 
+pub use self::fs::lib as fs;
 pub use self::io::lib as io;
+pub use self::json::lib as json;
 pub use self::net::lib as net;
 pub use self::os::lib as os;
+pub use self::path::lib as path;
+pub use self::process::lib as process;
+pub use self::regex::lib as regex;
+pub use self::string::lib as string;
 pub use self::time::lib as time;
@@ -1,14 +1,94 @@
-use std::env;
+use std::{env, process};
 
-use crate::runtime::{eval::Eval, heap::HeapObject, value::ShiroValue};
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::{FromShiro, IntoShiro},
+        heap::HeapObject,
+        native::Arity,
+        value::ShiroValue,
+        Runtime,
+    },
+};
 
-pub fn lib(obj: &mut HeapObject) {
-    obj.must_insert_fun("getenv", |args, scope, ctx| {
-        assert!(args.len() >= 1);
+use super::fs::io_error;
+
+#[cfg(unix)]
+fn hostname() -> Result<String, ShiroError> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for writes of its whole length
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        let err = std::io::Error::last_os_error();
+        return Err(format!("Cannot determine the host name: {}", err).into());
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Result<String, ShiroError> {
+    env::var("COMPUTERNAME").map_err(|_| "Cannot determine the host name".into())
+}
 
-        let key = args[0].eval(scope, ctx)?.coerce_string();
-        let val = env::var(&key).unwrap_or("".to_string());
+/// Functions that change the environment or working directory affect every
+/// runtime in the process, so the host has to allow them explicitly.
+fn check_process_changes(function: &str, ctx: &Runtime) -> Result<(), ShiroError> {
+    if ctx.allow_process_changes {
+        Ok(())
+    } else {
+        Err(ShiroError::GenericRuntimeError(format!(
+            "`os.{}` changes the whole process and is not allowed in this runtime",
+            function
+        )))
+    }
+}
 
-        Ok(ShiroValue::String(val))
+pub fn lib(obj: &mut HeapObject) {
+    obj.must_insert_typed_fun("getenv", |key: String| -> Result<String, ShiroError> {
+        Ok(env::var(&key).unwrap_or("".to_string()))
+    });
+    obj.must_insert_fun("setenv", Arity::Exact(2), |args, ctx| {
+        check_process_changes("setenv", ctx)?;
+        let key = String::from_shiro(&args[0], &ctx.heap)?;
+        let value = String::from_shiro(&args[1], &ctx.heap)?;
+        env::set_var(key, value);
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_fun("unsetenv", Arity::Exact(1), |args, ctx| {
+        check_process_changes("unsetenv", ctx)?;
+        let key = String::from_shiro(&args[0], &ctx.heap)?;
+        env::remove_var(key);
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_fun("args", Arity::Exact(0), |_, ctx| {
+        ctx.args.clone().into_shiro(&mut ctx.heap)
+    });
+    obj.must_insert_fun("exit", Arity::Between(0, 1), |args, ctx| {
+        let code = match args.first() {
+            Some(code) => i64::from_shiro(code, &ctx.heap)?,
+            None => 0,
+        };
+        // Codes that do not fit are reported as a plain failure
+        Err(ShiroError::Exit {
+            code: i32::try_from(code).unwrap_or(1),
+        })
+    });
+    obj.must_insert_typed_fun("cwd", || -> Result<String, ShiroError> {
+        let cwd = env::current_dir().map_err(|err| io_error(".", err))?;
+        Ok(cwd.to_string_lossy().into_owned())
+    });
+    obj.must_insert_fun("chdir", Arity::Exact(1), |args, ctx| {
+        check_process_changes("chdir", ctx)?;
+        let path = String::from_shiro(&args[0], &ctx.heap)?;
+        env::set_current_dir(&path).map_err(|err| io_error(&path, err))?;
+        Ok(ShiroValue::Null)
+    });
+    obj.must_insert_typed_fun("platform", || -> Result<String, ShiroError> {
+        Ok(env::consts::OS.to_string())
+    });
+    obj.must_insert_typed_fun("pid", || -> Result<i64, ShiroError> {
+        Ok(process::id() as i64)
     });
+    obj.must_insert_typed_fun("hostname", hostname);
 }
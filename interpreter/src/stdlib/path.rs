@@ -0,0 +1,125 @@
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    diag::ShiroError,
+    runtime::{convert::FromShiro, heap::HeapObject, native::Arity, value::ShiroValue},
+};
+
+use super::fs::io_error;
+
+fn to_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Resolves `.` and `..` without touching the file system. A `..` that would
+/// climb above the root is dropped, one at the start of a relative path is kept.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => result.push(".."),
+            },
+            other => result.push(other),
+        }
+    }
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+    result
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, ShiroError> {
+    if path.is_absolute() {
+        Ok(normalize(path))
+    } else {
+        let cwd = env::current_dir().map_err(|err| io_error(".", err))?;
+        Ok(normalize(&cwd.join(path)))
+    }
+}
+
+/// Returns `path` relative to `base`. Both are made absolute first; on a
+/// different drive the absolute path is returned as is.
+fn relative(path: &Path, base: &Path) -> Result<PathBuf, ShiroError> {
+    let path = absolute(path)?;
+    let base = absolute(base)?;
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+
+    if path_parts.peek() != base_parts.peek() {
+        return Ok(path);
+    }
+    while path_parts.peek().is_some() && path_parts.peek() == base_parts.peek() {
+        path_parts.next();
+        base_parts.next();
+    }
+
+    let mut result = PathBuf::new();
+    for _ in base_parts {
+        result.push("..");
+    }
+    result.extend(path_parts);
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+    Ok(result)
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    obj.must_insert_fun("join", Arity::AtLeast(1), |args, ctx| {
+        let mut path = PathBuf::new();
+        for arg in args {
+            path.push(String::from_shiro(arg, &ctx.heap)?);
+        }
+        Ok(ShiroValue::String(to_string(&path)))
+    });
+    obj.must_insert_typed_fun("dirname", |path: String| -> Result<String, ShiroError> {
+        Ok(Path::new(&path).parent().map(to_string).unwrap_or_default())
+    });
+    obj.must_insert_typed_fun("basename", |path: String| -> Result<String, ShiroError> {
+        Ok(Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default())
+    });
+    obj.must_insert_typed_fun("extension", |path: String| -> Result<String, ShiroError> {
+        Ok(Path::new(&path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default())
+    });
+    obj.must_insert_typed_fun("stem", |path: String| -> Result<String, ShiroError> {
+        Ok(Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default())
+    });
+    obj.must_insert_typed_fun("normalize", |path: String| -> Result<String, ShiroError> {
+        Ok(to_string(&normalize(Path::new(&path))))
+    });
+    obj.must_insert_typed_fun("absolute", |path: String| -> Result<String, ShiroError> {
+        Ok(to_string(&absolute(Path::new(&path))?))
+    });
+    obj.must_insert_fun("relative", Arity::Between(1, 2), |args, ctx| {
+        let path = String::from_shiro(&args[0], &ctx.heap)?;
+        let base = match args.get(1) {
+            Some(base) => PathBuf::from(String::from_shiro(base, &ctx.heap)?),
+            None => env::current_dir().map_err(|err| io_error(".", err))?,
+        };
+        Ok(ShiroValue::String(to_string(&relative(
+            Path::new(&path),
+            &base,
+        )?)))
+    });
+    obj.must_insert_typed_fun("is_absolute", |path: String| -> Result<bool, ShiroError> {
+        Ok(Path::new(&path).is_absolute())
+    });
+}
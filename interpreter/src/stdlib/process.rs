@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::FromShiro,
+        heap::{Heap, HeapObject},
+        native::Arity,
+        userdata::{UserData, UserDataType},
+        value::ShiroValue,
+        Runtime,
+    },
+};
+
+use super::fs::{io_error, lock, read_line};
+
+/// Builds a command from the `(cmd, args?, options?)` arguments shared by
+/// `run` and `spawn`. Returns the text to pipe into stdin, if any.
+fn command(args: &[ShiroValue], heap: &Heap) -> Result<(Command, Option<String>), ShiroError> {
+    let program = String::from_shiro(&args[0], heap)?;
+    let mut command = Command::new(program);
+    if let Some(arguments) = args.get(1) {
+        command.args(Vec::<String>::from_shiro(arguments, heap)?);
+    }
+
+    let mut stdin = None;
+    if let Some(options) = args.get(2) {
+        let options = HashMap::<String, ShiroValue>::from_shiro(options, heap)?;
+        if let Some(env) = options.get("env") {
+            command.envs(HashMap::<String, String>::from_shiro(env, heap)?);
+        }
+        if let Some(cwd) = options.get("cwd") {
+            command.current_dir(String::from_shiro(cwd, heap)?);
+        }
+        if let Some(input) = options.get("stdin") {
+            stdin = Some(String::from_shiro(input, heap)?);
+        }
+    }
+    Ok((command, stdin))
+}
+
+fn program_name(command: &Command) -> String {
+    command.get_program().to_string_lossy().into_owned()
+}
+
+/// The exit code, or null if the process was killed by a signal.
+fn status_code(status: ExitStatus) -> ShiroValue {
+    status
+        .code()
+        .map_or(ShiroValue::Null, |code| ShiroValue::Integer(code as i64))
+}
+
+/// Writes `input` to the child from another thread and closes its stdin
+/// afterwards, so that a child filling its output pipes cannot block us
+/// while we are still writing.
+fn feed_stdin(
+    stdin: Option<ChildStdin>,
+    input: Option<String>,
+) -> Option<JoinHandle<std::io::Result<()>>> {
+    match (stdin, input) {
+        (Some(mut stdin), Some(input)) => {
+            Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+        }
+        _ => None,
+    }
+}
+
+/// Waits for the writer started by [`feed_stdin`]. A child that exits
+/// without reading all of its input is not an error.
+fn finish_stdin(
+    name: &str,
+    writer: Option<JoinHandle<std::io::Result<()>>>,
+) -> Result<(), ShiroError> {
+    if let Some(writer) = writer {
+        match writer.join() {
+            Ok(Err(err)) if err.kind() != std::io::ErrorKind::BrokenPipe => {
+                return Err(io_error(name, err))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads what is left of an output pipe from another thread, so that a child
+/// writing more than the pipe can hold does not block while we wait for it.
+fn drain<R: Read + Send + 'static>(
+    reader: Option<R>,
+) -> Option<JoinHandle<std::io::Result<Vec<u8>>>> {
+    reader.map(|mut reader| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).map(|_| bytes)
+        })
+    })
+}
+
+/// Collects the output read by [`drain`], to be read by the script later.
+fn finish_drain(
+    name: &str,
+    reader: Option<JoinHandle<std::io::Result<Vec<u8>>>>,
+) -> Result<Option<Cursor<Vec<u8>>>, ShiroError> {
+    let Some(reader) = reader else {
+        return Ok(None);
+    };
+    match reader.join() {
+        Ok(Ok(bytes)) => Ok(Some(Cursor::new(bytes))),
+        Ok(Err(err)) => Err(io_error(name, err)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn run(args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let (mut command, input) = command(args, &ctx.heap)?;
+    let name = program_name(&command);
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| io_error(&name, err))?;
+
+    let writer = feed_stdin(child.stdin.take(), input);
+    let output = child
+        .wait_with_output()
+        .map_err(|err| io_error(&name, err))?;
+    finish_stdin(&name, writer)?;
+
+    let result = ctx.heap.alloc_object()?;
+    let mut result = result.borrow_mut();
+    result.try_insert("status", status_code(output.status))?;
+    result.try_insert("success", ShiroValue::Boolean(output.status.success()))?;
+    result.try_insert(
+        "stdout",
+        ShiroValue::String(String::from_utf8_lossy(&output.stdout).into_owned()),
+    )?;
+    result.try_insert(
+        "stderr",
+        ShiroValue::String(String::from_utf8_lossy(&output.stderr).into_owned()),
+    )?;
+    Ok(ShiroValue::HeapRef(result.address()))
+}
+
+/// A running child process. Stdin, stdout and stderr are pipes owned by the
+/// handle; the process is killed once the handle is collected. When spawned
+/// with the `stdin` option, the input is written by `writer` and stdin is
+/// closed afterwards, as with `run`. Output that is still unread when the
+/// script waits for the process is buffered and can be read afterwards.
+struct Process {
+    name: String,
+    child: Mutex<Child>,
+    writer: Mutex<Option<JoinHandle<std::io::Result<()>>>>,
+    stdin: Mutex<Option<ChildStdin>>,
+    stdout: Mutex<Option<Box<dyn BufRead + Send>>>,
+    stderr: Mutex<Option<Box<dyn Read + Send>>>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // A method that panicked while holding the lock leaves a child that
+        // still has to be cleaned up
+        let child = self.child.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if let Ok(None) = child.try_wait() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn process_type() -> UserDataType {
+    UserDataType::new("Process")
+        .method("pid", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            let pid = lock(&this.child).id();
+            Ok(ShiroValue::Integer(pid as i64))
+        })
+        .method("write", Arity::Exact(1), |this, args, ctx| {
+            let this = this.downcast::<Process>()?;
+            let text = String::from_shiro(&args[0], &ctx.heap)?;
+            let mut stdin = lock(&this.stdin);
+            let Some(stdin) = stdin.as_mut() else {
+                return Err("Cannot write to a closed stdin".into());
+            };
+            stdin
+                .write_all(text.as_bytes())
+                .and_then(|_| stdin.flush())
+                .map_err(|err| io_error(&this.name, err))?;
+            Ok(ShiroValue::Null)
+        })
+        .method("close_stdin", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            *lock(&this.stdin) = None;
+            Ok(ShiroValue::Null)
+        })
+        .method("read_line", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            let mut stdout = lock(&this.stdout);
+            let Some(reader) = stdout.as_mut() else {
+                return Ok(ShiroValue::Null);
+            };
+            match read_line(reader) {
+                Ok(Some(line)) => Ok(ShiroValue::String(line)),
+                Ok(None) => {
+                    *stdout = None;
+                    Ok(ShiroValue::Null)
+                }
+                Err(err) => Err(io_error(&this.name, err)),
+            }
+        })
+        .method("read_all", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            let mut text = String::new();
+            if let Some(mut reader) = lock(&this.stdout).take() {
+                reader
+                    .read_to_string(&mut text)
+                    .map_err(|err| io_error(&this.name, err))?;
+            }
+            Ok(ShiroValue::String(text))
+        })
+        .method("read_stderr", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            let mut text = String::new();
+            if let Some(mut reader) = lock(&this.stderr).take() {
+                reader
+                    .read_to_string(&mut text)
+                    .map_err(|err| io_error(&this.name, err))?;
+            }
+            Ok(ShiroValue::String(text))
+        })
+        .method("wait", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            // The child may be waiting for the end of its input, or for
+            // room in its output pipes
+            *lock(&this.stdin) = None;
+            let stdout = drain(lock(&this.stdout).take());
+            let stderr = drain(lock(&this.stderr).take());
+            let status = lock(&this.child)
+                .wait()
+                .map_err(|err| io_error(&this.name, err))?;
+            finish_stdin(&this.name, lock(&this.writer).take())?;
+
+            if let Some(stdout) = finish_drain(&this.name, stdout)? {
+                *lock(&this.stdout) = Some(Box::new(stdout));
+            }
+            if let Some(stderr) = finish_drain(&this.name, stderr)? {
+                *lock(&this.stderr) = Some(Box::new(stderr));
+            }
+            Ok(status_code(status))
+        })
+        .method("kill", Arity::Exact(0), |this, _, _| {
+            let this = this.downcast::<Process>()?;
+            let mut child = lock(&this.child);
+            if let Ok(None) = child.try_wait() {
+                child.kill().map_err(|err| io_error(&this.name, err))?;
+                child.wait().map_err(|err| io_error(&this.name, err))?;
+            }
+            Ok(ShiroValue::Null)
+        })
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    obj.must_insert_fun("run", Arity::Between(1, 3), run);
+
+    let process = Arc::new(process_type());
+    obj.must_insert_fun("spawn", Arity::Between(1, 3), move |args, ctx| {
+        let (mut command, input) = command(args, &ctx.heap)?;
+        let name = program_name(&command);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| io_error(&name, err))?;
+
+        let (stdin, writer) = match input {
+            Some(input) => (None, feed_stdin(child.stdin.take(), Some(input))),
+            None => (child.stdin.take(), None),
+        };
+        let handle = Process {
+            name,
+            writer: Mutex::new(writer),
+            stdout: Mutex::new(
+                child
+                    .stdout
+                    .take()
+                    .map(|stdout| Box::new(BufReader::new(stdout)) as Box<dyn BufRead + Send>),
+            ),
+            stderr: Mutex::new(
+                child
+                    .stderr
+                    .take()
+                    .map(|stderr| Box::new(stderr) as Box<dyn Read + Send>),
+            ),
+            stdin: Mutex::new(stdin),
+            child: Mutex::new(child),
+        };
+        ctx.heap.alloc_userdata(UserData::new(&process, handle))
+    });
+}
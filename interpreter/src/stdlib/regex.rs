@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use regex::{Captures, Match, Regex};
+
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::{FromShiro, IntoShiro},
+        heap::HeapObject,
+        native::Arity,
+        userdata::{UserData, UserDataType},
+        value::ShiroValue,
+        Runtime,
+    },
+};
+
+/// Patterns passed to the module functions as strings are compiled once and
+/// kept here. The cache is simply emptied when it grows past this size.
+const CACHE_SIZE: usize = 64;
+
+/// A regex function, called with the compiled pattern and the remaining
+/// arguments.
+type RegexFn = fn(&Regex, &[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>;
+
+/// The functions that are also methods of compiled patterns. The arity does
+/// not count the pattern itself.
+const FUNCTIONS: &[(&str, Arity, RegexFn)] = &[
+    ("is_match", Arity::Exact(1), is_match),
+    ("find", Arity::Exact(1), find),
+    ("find_all", Arity::Exact(1), find_all),
+    ("captures", Arity::Exact(1), captures),
+    ("replace", Arity::Exact(2), replace),
+    ("replace_all", Arity::Exact(2), replace_all),
+    ("split", Arity::Exact(1), split),
+];
+
+fn compile(pattern: &str) -> Result<Regex, ShiroError> {
+    Regex::new(pattern).map_err(|err| {
+        ShiroError::GenericRuntimeError(format!("Invalid regular expression: {}", err))
+    })
+}
+
+fn text(args: &[ShiroValue], ctx: &Runtime) -> Result<String, ShiroError> {
+    String::from_shiro(&args[0], &ctx.heap)
+}
+
+/// Converts byte offsets into `text` to character indices, which is what
+/// string indexing and `@std/string` use. Counting continues from the
+/// previous offset, so that the text is only counted once for all matches.
+struct CharIndices<'a> {
+    text: &'a str,
+    byte: usize,
+    index: i64,
+}
+
+impl<'a> CharIndices<'a> {
+    fn new(text: &'a str) -> Self {
+        CharIndices {
+            text,
+            byte: 0,
+            index: 0,
+        }
+    }
+
+    fn at(&mut self, byte: usize) -> i64 {
+        if byte < self.byte {
+            self.byte = 0;
+            self.index = 0;
+        }
+        self.index += self.text[self.byte..byte].chars().count() as i64;
+        self.byte = byte;
+        self.index
+    }
+}
+
+/// Describes a match as an object with its `text` and its `start` and `end`
+/// character indices.
+fn match_object(
+    m: Match,
+    indices: &mut CharIndices,
+    ctx: &mut Runtime,
+) -> Result<ShiroValue, ShiroError> {
+    let obj = ctx.heap.alloc_object()?;
+    let mut obj = obj.borrow_mut();
+    obj.try_insert("text", ShiroValue::String(m.as_str().to_string()))?;
+    obj.try_insert("start", ShiroValue::Integer(indices.at(m.start())))?;
+    obj.try_insert("end", ShiroValue::Integer(indices.at(m.end())))?;
+    Ok(ShiroValue::HeapRef(obj.address()))
+}
+
+fn is_match(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::Boolean(re.is_match(&text(args, ctx)?)))
+}
+
+fn find(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    match re.find(&text) {
+        Some(m) => match_object(m, &mut CharIndices::new(&text), ctx),
+        None => Ok(ShiroValue::Null),
+    }
+}
+
+fn find_all(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    let mut indices = CharIndices::new(&text);
+    let mut matches = Vec::new();
+    for m in re.find_iter(&text) {
+        matches.push(match_object(m, &mut indices, ctx)?);
+    }
+    matches.into_shiro(&mut ctx.heap)
+}
+
+/// The groups of the first match, or null. Every group is a field named by
+/// its number (`0` being the whole match), named groups also by their name.
+/// Groups that did not take part in the match are null.
+fn captures(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    let Some(caps) = re.captures(&text) else {
+        return Ok(ShiroValue::Null);
+    };
+    let group = |caps: &Captures, i: usize| {
+        caps.get(i).map_or(ShiroValue::Null, |m| {
+            ShiroValue::String(m.as_str().to_string())
+        })
+    };
+
+    let obj = ctx.heap.alloc_object()?;
+    let mut obj = obj.borrow_mut();
+    for (i, name) in re.capture_names().enumerate() {
+        obj.try_insert(&i.to_string(), group(&caps, i))?;
+        if let Some(name) = name {
+            obj.try_insert(name, group(&caps, i))?;
+        }
+    }
+    Ok(ShiroValue::HeapRef(obj.address()))
+}
+
+/// Replaces the first match. The replacement may refer to groups as `$1` or
+/// `$name`.
+fn replace(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    let replacement = String::from_shiro(&args[1], &ctx.heap)?;
+    Ok(ShiroValue::String(
+        re.replace(&text, replacement.as_str()).into_owned(),
+    ))
+}
+
+fn replace_all(
+    re: &Regex,
+    args: &[ShiroValue],
+    ctx: &mut Runtime,
+) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    let replacement = String::from_shiro(&args[1], &ctx.heap)?;
+    Ok(ShiroValue::String(
+        re.replace_all(&text, replacement.as_str()).into_owned(),
+    ))
+}
+
+fn split(re: &Regex, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let text = text(args, ctx)?;
+    let parts: Vec<String> = re.split(&text).map(str::to_string).collect();
+    parts.into_shiro(&mut ctx.heap)
+}
+
+fn regex_type() -> UserDataType {
+    let mut ty = UserDataType::new("Regex").method("source", Arity::Exact(0), |this, _, _| {
+        let re = this.downcast::<Regex>()?;
+        Ok(ShiroValue::String(re.as_str().to_string()))
+    });
+    for (name, arity, body) in FUNCTIONS {
+        ty = ty.method(name, *arity, move |this, args, ctx| {
+            body(this.downcast::<Regex>()?, args, ctx)
+        });
+    }
+    ty
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    let regex = Arc::new(regex_type());
+    obj.must_insert_fun("compile", Arity::Exact(1), move |args, ctx| {
+        let pattern = String::from_shiro(&args[0], &ctx.heap)?;
+        ctx.heap
+            .alloc_userdata(UserData::new(&regex, compile(&pattern)?))
+    });
+
+    // The module functions take either a compiled pattern or a pattern string
+    let cache = Arc::new(Mutex::new(HashMap::<String, Regex>::new()));
+    for (name, arity, body) in FUNCTIONS {
+        let cache = cache.clone();
+        obj.must_insert_fun(name, arity.with_leading(1), move |args, ctx| {
+            let re = match &args[0] {
+                ShiroValue::String(pattern) => {
+                    // The cache only ever holds complete entries, so one
+                    // left behind by a panic is still fine to use
+                    let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+                    match cache.get(pattern) {
+                        Some(re) => re.clone(),
+                        None => {
+                            let re = compile(pattern)?;
+                            if cache.len() >= CACHE_SIZE {
+                                cache.clear();
+                            }
+                            cache.insert(pattern.clone(), re.clone());
+                            re
+                        }
+                    }
+                }
+                other => UserData::from_shiro(other, &ctx.heap)?
+                    .downcast::<Regex>()?
+                    .clone(),
+            };
+            body(&re, &args[1..], ctx)
+        });
+    }
+}
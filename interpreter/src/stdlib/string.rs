@@ -0,0 +1,199 @@
+use crate::{
+    diag::ShiroError,
+    runtime::{
+        convert::{FromShiro, IntoShiro},
+        heap::HeapObject,
+        native::{Arity, NativeFunction},
+        value::ShiroValue,
+        Runtime,
+    },
+};
+
+/// A string function, called with the string it operates on and the
+/// remaining arguments.
+type StringFn = fn(&str, &[ShiroValue], &mut Runtime) -> Result<ShiroValue, ShiroError>;
+
+/// The functions that are also methods of string values. The arity does not
+/// count the string itself.
+const FUNCTIONS: &[(&str, Arity, StringFn)] = &[
+    ("split", Arity::Between(0, 1), split),
+    ("trim", Arity::Exact(0), trim),
+    ("replace", Arity::Exact(2), replace),
+    ("find", Arity::Exact(1), find),
+    ("starts_with", Arity::Exact(1), starts_with),
+    ("ends_with", Arity::Exact(1), ends_with),
+    ("upper", Arity::Exact(0), upper),
+    ("lower", Arity::Exact(0), lower),
+    ("repeat", Arity::Exact(1), repeat),
+    ("pad_left", Arity::Between(1, 2), pad_left),
+    ("pad_right", Arity::Between(1, 2), pad_right),
+    ("substring", Arity::Between(1, 2), substring),
+    ("chars", Arity::Exact(0), chars),
+];
+
+/// The longest string, in bytes, that `repeat` and the padding functions
+/// build. Larger results are an error rather than an allocation failure.
+const MAX_RESULT_LEN: usize = 1 << 30;
+
+/// Checks the length of a result, which is `None` if it overflowed.
+fn check_result_len(len: Option<usize>, what: impl FnOnce() -> String) -> Result<(), ShiroError> {
+    match len {
+        Some(len) if len <= MAX_RESULT_LEN => Ok(()),
+        _ => Err(format!("{}, the result would be too large", what()).into()),
+    }
+}
+
+fn arg<T: FromShiro>(args: &[ShiroValue], index: usize, ctx: &Runtime) -> Result<T, ShiroError> {
+    T::from_shiro(&args[index], &ctx.heap)
+}
+
+/// Splits on `sep`, or on runs of whitespace without one.
+fn split(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let parts: Vec<String> = match args.first() {
+        Some(_) => {
+            let sep: String = arg(args, 0, ctx)?;
+            if sep.is_empty() {
+                return Err("Cannot split on an empty separator".into());
+            }
+            this.split(sep.as_str()).map(str::to_string).collect()
+        }
+        None => this.split_whitespace().map(str::to_string).collect(),
+    };
+    parts.into_shiro(&mut ctx.heap)
+}
+
+fn trim(this: &str, _: &[ShiroValue], _: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::String(this.trim().to_string()))
+}
+
+/// Replaces every occurrence of the first argument.
+fn replace(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let from: String = arg(args, 0, ctx)?;
+    let to: String = arg(args, 1, ctx)?;
+    Ok(ShiroValue::String(this.replace(&from, &to)))
+}
+
+/// The character index of the first occurrence, or -1.
+fn find(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let needle: String = arg(args, 0, ctx)?;
+    let index = this
+        .find(&needle)
+        .map_or(-1, |byte| this[..byte].chars().count() as i64);
+    Ok(ShiroValue::Integer(index))
+}
+
+fn starts_with(
+    this: &str,
+    args: &[ShiroValue],
+    ctx: &mut Runtime,
+) -> Result<ShiroValue, ShiroError> {
+    let prefix: String = arg(args, 0, ctx)?;
+    Ok(ShiroValue::Boolean(this.starts_with(&prefix)))
+}
+
+fn ends_with(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let suffix: String = arg(args, 0, ctx)?;
+    Ok(ShiroValue::Boolean(this.ends_with(&suffix)))
+}
+
+fn upper(this: &str, _: &[ShiroValue], _: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::String(this.to_uppercase()))
+}
+
+fn lower(this: &str, _: &[ShiroValue], _: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::String(this.to_lowercase()))
+}
+
+fn repeat(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let count: i64 = arg(args, 0, ctx)?;
+    if count < 0 {
+        return Err(format!("Cannot repeat a string {} times", count).into());
+    }
+    check_result_len(this.len().checked_mul(count as usize), || {
+        format!("Cannot repeat a string {} times", count)
+    })?;
+    Ok(ShiroValue::String(this.repeat(count as usize)))
+}
+
+/// The padding that brings `this` to `width` characters, made of the
+/// optional fill character (a space by default).
+fn padding(this: &str, args: &[ShiroValue], ctx: &Runtime) -> Result<String, ShiroError> {
+    let width: i64 = arg(args, 0, ctx)?;
+    let fill = match args.get(1) {
+        Some(_) => arg::<String>(args, 1, ctx)?,
+        None => " ".to_string(),
+    };
+    if fill.chars().count() != 1 {
+        return Err(format!("Padding must be a single character, not `{}`", fill).into());
+    }
+    let missing = (width.max(0) as usize).saturating_sub(this.chars().count());
+    let len = fill
+        .len()
+        .checked_mul(missing)
+        .and_then(|len| len.checked_add(this.len()));
+    check_result_len(len, || {
+        format!("Cannot pad a string to {} characters", width)
+    })?;
+    Ok(fill.repeat(missing))
+}
+
+fn pad_left(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::String(padding(this, args, ctx)? + this))
+}
+
+fn pad_right(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    Ok(ShiroValue::String(
+        this.to_string() + &padding(this, args, ctx)?,
+    ))
+}
+
+/// The characters from `start` up to, but not including, `end` (or the end
+/// of the string). Both are clamped to the length of the string.
+fn substring(this: &str, args: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let len = this.chars().count() as i64;
+    let start = arg::<i64>(args, 0, ctx)?.clamp(0, len);
+    let end = match args.get(1) {
+        Some(_) => arg::<i64>(args, 1, ctx)?.clamp(start, len),
+        None => len,
+    };
+    let result = this
+        .chars()
+        .skip(start as usize)
+        .take((end - start) as usize)
+        .collect();
+    Ok(ShiroValue::String(result))
+}
+
+fn chars(this: &str, _: &[ShiroValue], ctx: &mut Runtime) -> Result<ShiroValue, ShiroError> {
+    let chars: Vec<char> = this.chars().collect();
+    chars.into_shiro(&mut ctx.heap)
+}
+
+/// Looks up a string function as a method bound to `this`, so that
+/// `s.split(',')` calls `split` on `s`.
+pub fn method(this: &str, name: &str) -> Option<NativeFunction> {
+    let (name, arity, body) = FUNCTIONS.iter().find(|(fun, _, _)| *fun == name)?;
+    let this = this.to_string();
+    Some(NativeFunction::new(name, *arity, move |args, ctx| {
+        body(&this, args, ctx)
+    }))
+}
+
+pub fn lib(obj: &mut HeapObject) {
+    for (name, arity, body) in FUNCTIONS {
+        obj.must_insert_fun(name, arity.with_leading(1), move |args, ctx| {
+            let this: String = arg(args, 0, ctx)?;
+            body(&this, &args[1..], ctx)
+        });
+    }
+    // Not a method, as it operates on an array rather than a string
+    obj.must_insert_fun("join", Arity::Between(1, 2), |args, ctx| {
+        let items: Vec<ShiroValue> = arg(args, 0, ctx)?;
+        let sep = match args.get(1) {
+            Some(_) => arg::<String>(args, 1, ctx)?,
+            None => String::new(),
+        };
+        let items: Vec<String> = items.iter().map(ShiroValue::coerce_string).collect();
+        Ok(ShiroValue::String(items.join(&sep)))
+    });
+}
@@ -1,9 +1,9 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::runtime::{heap::HeapObject, value::ShiroValue};
+use crate::runtime::{heap::HeapObject, native::Arity, value::ShiroValue};
 
 pub fn lib(obj: &mut HeapObject) {
-    obj.must_insert_fun("millis", |_, _, _| {
+    obj.must_insert_fun("millis", Arity::Exact(0), |_, _| {
         let unix_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Get out of your time machine.");
@@ -0,0 +1,80 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use shiro_interpreter::{Runtime, ShiroValue};
+
+const TEST_PREFIX: &str = "test_";
+
+/// Collects the `.shiro` files at the given paths, searching directories
+/// recursively.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "shiro") {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Runs every global function whose name starts with `test_` in each of
+/// the test files, each file in a runtime of its own. A test fails when it
+/// raises an error, e.g. through `assert`. Returns whether all tests passed.
+pub fn run(paths: &[PathBuf], mut new_runtime: impl FnMut() -> Runtime) -> bool {
+    let mut files = Vec::new();
+    for path in paths {
+        if let Err(err) = collect_files(path, &mut files) {
+            eprintln!("shiro: Cannot read `{}`: {}", path.display(), err);
+            return false;
+        }
+    }
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for file in files {
+        let mut rt = new_runtime();
+        if let Err(error) = rt.eval_path(&file.to_string_lossy()) {
+            println!("test {} ... FAILED", file.display());
+            rt.report_error(error);
+            failed += 1;
+            continue;
+        }
+
+        let mut tests: Vec<String> = rt
+            .global_names()
+            .into_iter()
+            .filter(|name| name.starts_with(TEST_PREFIX))
+            .filter(|name| matches!(rt.get_global(name), ShiroValue::Function { .. }))
+            .collect();
+        tests.sort();
+
+        for test in tests {
+            match rt.call_global(&test, vec![]) {
+                Ok(_) => {
+                    println!("test {}::{} ... ok", file.display(), test);
+                    passed += 1;
+                }
+                Err(error) => {
+                    println!("test {}::{} ... FAILED", file.display(), test);
+                    rt.report_error(error);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        passed,
+        failed
+    );
+    failed == 0
+}
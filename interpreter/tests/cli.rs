@@ -0,0 +1,180 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+mod common;
+
+use common::project;
+
+fn shiro(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_shiro-interpreter"))
+        .args(args)
+        .output()
+        .expect("Failed to run interpreter")
+}
+
+#[test]
+fn eval_prints_the_result() {
+    let output = shiro(&["eval", "-e", "let x = 20; x * 2 + 2;"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "42");
+}
+
+#[test]
+fn dbg_prints_each_value() {
+    let output = shiro(&["eval", "-e", "dbg(1 + 2, 'text');"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, ["[dbg] Integer(3)", "[dbg] String(\"text\")"]);
+}
+
+#[test]
+fn failing_script_exits_with_an_error() {
+    let output = shiro(&["eval", "-e", "missing();"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+
+    let output = shiro(&["--strict", "eval", "-e", "undefined_var;"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn repl_keeps_state_between_inputs() {
+    // Keeps the history file out of the real home directory
+    let home = env::temp_dir().join(format!("shiro-repl-home-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shiro-interpreter"))
+        .arg("repl")
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run interpreter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"let x = 20;\nfunc twice(n) {\n    return n * 2;\n}\ntwice(x) + 2\nmissing();\n'still running';\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, ["20", "42", "still running"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+    assert!(home.join(".shiro_history").is_file());
+}
+
+fn path_arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn check_reports_syntax_errors() {
+    let dir = project(
+        "check",
+        &[("good.shiro", "missing();"), ("bad.shiro", "let x = ;")],
+    );
+    // Only syntax is checked, so the call to an undefined function passes
+    let output = shiro(&["check", path_arg(&dir.join("good.shiro"))]);
+    assert!(output.status.success());
+
+    let output = shiro(&[
+        "check",
+        path_arg(&dir.join("good.shiro")),
+        path_arg(&dir.join("bad.shiro")),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bad.shiro"));
+}
+
+#[test]
+fn fmt_rewrites_unformatted_files() {
+    let unformatted =
+        "func f() {\nreturn 1;   \n  }\n\n\n\n# { not a bracket\nlet s = '{';\nf();\n";
+    let formatted = "func f() {\n    return 1;\n}\n\n# { not a bracket\nlet s = '{';\nf();\n";
+    let dir = project("fmt", &[("main.shiro", unformatted)]);
+    let file = dir.join("main.shiro");
+
+    let output = shiro(&["fmt", "--check", path_arg(&file)]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("is not formatted"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), unformatted);
+
+    let output = shiro(&["fmt", path_arg(&file)]);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), formatted);
+
+    let output = shiro(&["fmt", "--check", path_arg(&file)]);
+    assert!(output.status.success());
+
+    // Files with syntax errors are left alone
+    fs::write(&file, "let x = ;\n   f();\n").unwrap();
+    let output = shiro(&["fmt", path_arg(&file)]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "let x = ;\n   f();\n");
+}
+
+#[test]
+fn test_runs_test_functions() {
+    let dir = project(
+        "test",
+        &[
+            (
+                "tests/math.shiro",
+                "func test_adds() { assert(1 + 1 == 2); }\nfunc helper() { assert(false); }",
+            ),
+            (
+                "tests/nested/fails.shiro",
+                "func test_fails() { assert(1 == 2, 'not equal'); }",
+            ),
+        ],
+    );
+    let output = shiro(&["test", path_arg(&dir.join("tests/math.shiro"))]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("math.shiro::test_adds ... ok"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("helper"), "{}", stdout);
+
+    let output = shiro(&["test", path_arg(&dir.join("tests"))]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("fails.shiro::test_fails ... FAILED"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("1 passed; 1 failed"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not equal"));
+}
+
+#[test]
+fn arguments_after_double_dash_reach_the_script() {
+    let code = "import '@std/os' as os; let args = os.args(); '' + len(args) + ':' + args[0] + ',' + args[1];";
+    let output = shiro(&["eval", "-e", code, "--", "first", "--strict"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "2:first,--strict"
+    );
+
+    let dir = project("args", &[("main.shiro", code)]);
+    let file = dir.join("main.shiro");
+    let output = shiro(&["run", path_arg(&file), "--", "a", "b"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2:a,b");
+    let output = shiro(&[path_arg(&file), "--", "c", "d"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2:c,d");
+}
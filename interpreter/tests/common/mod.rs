@@ -0,0 +1,47 @@
+//! Helpers shared by the integration tests. Each test uses only some of them.
+#![allow(dead_code)]
+
+use std::{env, fs, path::PathBuf};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+/// Writes `files` into a fresh directory for the test called `name`.
+pub fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "shiro-{}-{}-{}",
+        env!("CARGO_CRATE_NAME"),
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    fs::canonicalize(dir).unwrap()
+}
+
+/// Evaluates `code` in `rt` with each of the standard libraries `libs`
+/// imported under its own name.
+pub fn eval_in(rt: &mut Runtime, libs: &[&str], code: &str) -> Result<ShiroValue, ShiroError> {
+    let imports: String = libs
+        .iter()
+        .map(|lib| format!("import '@std/{0}' as {0};\n", lib))
+        .collect();
+    rt.eval_str("test", &(imports + code))
+}
+
+/// Evaluates `code` in a fresh runtime, see [`eval_in`].
+pub fn eval(libs: &[&str], code: &str) -> Result<ShiroValue, ShiroError> {
+    eval_in(&mut Runtime::new(), libs, code)
+}
+
+/// The string a script evaluated to. Anything else fails the test.
+pub fn expect_string(result: Result<ShiroValue, ShiroError>) -> String {
+    match result.expect("Script failed") {
+        ShiroValue::String(s) => s,
+        other => panic!("Expected a string, got {:?}", other),
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use shiro_interpreter::{Runtime, ShiroValue};
+
+const WORKERS: usize = 8;
+const JOBS: i64 = 64;
+
+const SCRIPT: &str = "
+let items = [];
+for let i = 0; i < 1000; i += 1 {
+    append(items, { value: i * id });
+}
+let total = 0;
+for item in items {
+    total += item.value;
+}
+total;
+";
+
+/// Runs every job on a fixed number of worker threads, each job on a
+/// runtime of its own, and returns the results by job id.
+fn run_on_pool(runtimes: Vec<(i64, Runtime)>) -> Vec<(i64, i64)> {
+    let (job_tx, job_rx) = mpsc::channel::<(i64, Runtime)>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (result_tx, result_rx) = mpsc::channel();
+
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let job_rx = job_rx.clone();
+            let result_tx = result_tx.clone();
+            thread::spawn(move || loop {
+                let job = job_rx.lock().unwrap().recv();
+                let Ok((id, mut rt)) = job else {
+                    break;
+                };
+                let result = rt.eval_str("job", SCRIPT).expect("Script failed");
+                let ShiroValue::Integer(total) = result else {
+                    panic!("Expected an integer, got {:?}", result);
+                };
+                // The heap of this runtime holds only its own objects
+                assert!(rt.heap.object_count() <= 1001);
+                result_tx.send((id, total)).unwrap();
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    for job in runtimes {
+        job_tx.send(job).unwrap();
+    }
+    drop(job_tx);
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let mut results: Vec<_> = result_rx.into_iter().collect();
+    results.sort();
+    results
+}
+
+#[test]
+fn runtimes_run_concurrently_in_isolation() {
+    // The runtimes are set up on this thread and moved to the workers
+    let runtimes = (0..JOBS)
+        .map(|id| {
+            let mut rt = Runtime::new();
+            rt.set_global("id", ShiroValue::Integer(id));
+            (id, rt)
+        })
+        .collect();
+
+    let results = run_on_pool(runtimes);
+
+    let expected: Vec<_> = (0..JOBS).map(|id| (id, id * 999 * 1000 / 2)).collect();
+    assert_eq!(results, expected);
+}
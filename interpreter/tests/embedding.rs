@@ -0,0 +1,100 @@
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+#[test]
+fn eval_str_returns_the_last_value() {
+    let mut rt = Runtime::new();
+    let result = rt.eval_str("sum", "let a = 2; a * 21;").unwrap();
+    assert_eq!(result, ShiroValue::Integer(42));
+
+    let result = rt.eval_str("syntax", "let = ;");
+    assert!(result.is_err());
+}
+
+#[test]
+fn globals_are_shared_with_the_host() {
+    let mut rt = Runtime::new();
+    rt.eval_str("define", "let greeting = 'hello';").unwrap();
+    assert_eq!(
+        rt.get_global("greeting"),
+        ShiroValue::String("hello".to_string())
+    );
+    assert!(matches!(rt.get_global("missing"), ShiroValue::Null));
+
+    rt.set_global("limit", ShiroValue::Integer(10));
+    let result = rt.eval_str("use", "limit + 1;").unwrap();
+    assert_eq!(result, ShiroValue::Integer(11));
+    assert!(rt.global_names().contains(&"limit".to_string()));
+}
+
+#[test]
+fn host_calls_script_functions() {
+    let mut rt = Runtime::new();
+    rt.eval_str("define", "func add(a, b) { return a + b; }")
+        .unwrap();
+
+    let result = rt
+        .call_global("add", vec![ShiroValue::Integer(1), ShiroValue::Integer(2)])
+        .unwrap();
+    assert_eq!(result, ShiroValue::Integer(3));
+
+    let add = rt.get_global("add");
+    let result = rt
+        .call(&add, vec![ShiroValue::Integer(4), ShiroValue::Integer(5)])
+        .unwrap();
+    assert_eq!(result, ShiroValue::Integer(9));
+
+    let result = rt.call_global("missing", vec![]);
+    assert!(
+        matches!(result, Err(ShiroError::GenericRuntimeError(_))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn rooted_values_survive_later_evaluations() {
+    let mut rt = Runtime::new();
+    let kept = rt.eval_str("make", "{ name: 'kept' };").unwrap();
+    let ShiroValue::HeapRef(addr) = kept else {
+        panic!("Expected an object, got {:?}", kept);
+    };
+    rt.root(&kept);
+
+    rt.eval_str("churn", "for let i = 0; i < 10; i += 1 { {}; }")
+        .unwrap();
+    rt.eval_str("churn", "1;").unwrap();
+    assert!(rt.heap.deref(addr).is_ok());
+
+    // The rooted object can be handed back to scripts
+    rt.set_global("kept", kept.clone());
+    let name = rt.eval_str("read", "kept.name;").unwrap();
+    assert_eq!(name, ShiroValue::String("kept".to_string()));
+
+    // Once released and no longer a global, it is collected
+    rt.unroot(&kept);
+    rt.set_global("kept", ShiroValue::Null);
+    rt.eval_str("churn", "1;").unwrap();
+    assert!(matches!(
+        rt.heap.deref(addr),
+        Err(ShiroError::DanglingReference { .. })
+    ));
+}
+
+#[test]
+fn rooted_closures_keep_their_captured_objects() {
+    let mut rt = Runtime::new();
+    let counter = rt
+        .eval_str(
+            "make",
+            "func make() { let state = { n: 0 }; return func() { state.n += 1; return state.n; }; } make();",
+        )
+        .unwrap();
+    rt.root(&counter);
+
+    for expected in 1..=3 {
+        rt.eval_str("churn", "1;").unwrap();
+        let result = rt.call(&counter, vec![]).unwrap();
+        assert_eq!(result, ShiroValue::Integer(expected));
+    }
+    rt.unroot(&counter);
+}
@@ -0,0 +1,150 @@
+use std::{fs, path::PathBuf};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+/// A fresh directory for the test called `name`, also available to scripts
+/// as the global `dir`.
+fn setup(name: &str) -> (Runtime, PathBuf) {
+    let dir = common::project(name, &[]);
+    let mut rt = Runtime::new();
+    rt.set_global(
+        "dir",
+        ShiroValue::String(dir.to_string_lossy().into_owned()),
+    );
+    rt.eval_str("import", "import '@std/fs' as fs;").unwrap();
+    (rt, dir)
+}
+
+fn eval_string(rt: &mut Runtime, code: &str) -> String {
+    common::expect_string(rt.eval_str("fs_test", code))
+}
+
+#[test]
+fn write_append_and_read() {
+    let (mut rt, dir) = setup("text");
+    let out = eval_string(
+        &mut rt,
+        "
+        let file = dir + '/notes.txt';
+        fs.write_text(file, 'one');
+        fs.append(file, ' two');
+        fs.append(dir + '/new.txt', 'created');
+        fs.read_text(file) + '|' + fs.read_text(dir + '/new.txt');
+        ",
+    );
+    assert_eq!(out, "one two|created");
+    assert_eq!(
+        fs::read_to_string(dir.join("notes.txt")).unwrap(),
+        "one two"
+    );
+
+    let bytes = eval_string(
+        &mut rt,
+        "let bytes = fs.read_bytes(dir + '/new.txt'); '' + len(bytes) + ':' + bytes[0];",
+    );
+    assert_eq!(bytes, "7:99");
+}
+
+#[test]
+fn stat_and_list_dir() {
+    let (mut rt, dir) = setup("stat");
+    fs::write(dir.join("b.txt"), "12345").unwrap();
+    fs::write(dir.join("a.txt"), "").unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+
+    let out = eval_string(
+        &mut rt,
+        "
+        let file = fs.stat(dir + '/b.txt');
+        let sub = fs.stat(dir + '/sub');
+        let names = fs.list_dir(dir);
+        '' + file.size + ',' + file.is_file + ',' + file.is_dir + ',' + sub.is_dir + ','
+            + typeof(file.modified) + ':' + names[0] + ',' + names[1] + ',' + names[2];
+        ",
+    );
+    assert_eq!(out, "5,true,false,true,integer:a.txt,b.txt,sub");
+}
+
+#[test]
+fn rename_copy_and_remove() {
+    let (mut rt, dir) = setup("move");
+    let out = eval_string(
+        &mut rt,
+        "
+        fs.write_text(dir + '/a.txt', 'content');
+        fs.copy(dir + '/a.txt', dir + '/b.txt');
+        fs.rename(dir + '/a.txt', dir + '/c.txt');
+        fs.mkdir_all(dir + '/x/y');
+        fs.write_text(dir + '/x/y/z.txt', 'deep');
+        let before = '' + fs.exists(dir + '/a.txt') + ',' + fs.read_text(dir + '/b.txt') + ',' + fs.read_text(dir + '/c.txt');
+        fs.remove(dir + '/b.txt');
+        fs.remove(dir + '/x', true);
+        before + ':' + fs.exists(dir + '/b.txt') + ',' + fs.exists(dir + '/x');
+        ",
+    );
+    assert_eq!(out, "false,content,content:false,false");
+
+    // A directory that is not empty is only removed when asked to
+    fs::create_dir_all(dir.join("full")).unwrap();
+    fs::write(dir.join("full/file"), "").unwrap();
+    let result = rt.eval_str("remove", "fs.remove(dir + '/full');");
+    assert!(
+        matches!(result, Err(ShiroError::IoError { .. })),
+        "{:?}",
+        result
+    );
+    assert!(dir.join("full/file").exists());
+}
+
+#[test]
+fn lines_reads_one_line_at_a_time() {
+    let (mut rt, dir) = setup("lines");
+    fs::write(dir.join("input.txt"), "first\r\n\nthird\nlast").unwrap();
+    let out = eval_string(
+        &mut rt,
+        "
+        let reader = fs.lines(dir + '/input.txt');
+        let out = typeof(reader) + ':';
+        let line = reader.next();
+        while typeof(line) != 'null' {
+            out += '[' + line + ']';
+            line = reader.next();
+        }
+        out + typeof(reader.next());
+        ",
+    );
+    assert_eq!(out, "LineReader:[first][][third][last]null");
+
+    let out = eval_string(
+        &mut rt,
+        "let reader = fs.lines(dir + '/input.txt'); reader.close(); typeof(reader.next());",
+    );
+    assert_eq!(out, "null");
+}
+
+#[test]
+fn failures_are_io_errors() {
+    let (mut rt, dir) = setup("errors");
+    let missing = dir.join("missing.txt").to_string_lossy().into_owned();
+    for call in [
+        "fs.read_text(dir + '/missing.txt')",
+        "fs.read_bytes(dir + '/missing.txt')",
+        "fs.stat(dir + '/missing.txt')",
+        "fs.list_dir(dir + '/missing.txt')",
+        "fs.remove(dir + '/missing.txt')",
+        "fs.rename(dir + '/missing.txt', dir + '/other.txt')",
+        "fs.copy(dir + '/missing.txt', dir + '/other.txt')",
+        "fs.lines(dir + '/missing.txt')",
+        "fs.write_text(dir + '/missing.txt/nested', 'x')",
+    ] {
+        let result = rt.eval_str("fs_test", &format!("{};", call));
+        assert!(
+            matches!(&result, Err(ShiroError::IoError { path, .. }) if path.starts_with(&missing)),
+            "{}: {:?}",
+            call,
+            result
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+#[test]
+fn collected_objects_are_dangling_references() {
+    let mut rt = Runtime::new();
+    let ShiroValue::HeapRef(addr) = rt
+        .eval_str("make", "func make() { return { x: 1 }; } make();")
+        .unwrap()
+    else {
+        panic!("Expected an object");
+    };
+    assert!(rt.heap.deref(addr).is_ok());
+
+    // Nothing refers to the object any more once the next evaluation ends
+    rt.eval_str("next", "1;").unwrap();
+    assert!(matches!(
+        rt.heap.deref(addr),
+        Err(ShiroError::DanglingReference { .. })
+    ));
+
+    // A new object in the same slot does not bring the old address back
+    rt.eval_str("reuse", "let kept = { y: 2 };").unwrap();
+    assert!(matches!(
+        rt.heap.deref(addr),
+        Err(ShiroError::DanglingReference { .. })
+    ));
+}
@@ -0,0 +1,41 @@
+use std::{thread, time::Duration};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+#[test]
+fn interrupt_stops_a_running_script() {
+    let mut rt = Runtime::new();
+    let handle = rt.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+
+    let result = rt.eval_str("loop", "while true { }");
+    interrupter.join().unwrap();
+    assert!(
+        matches!(result, Err(ShiroError::Interrupted)),
+        "{:?}",
+        result
+    );
+
+    // The runtime can be used again afterwards
+    let result = rt.eval_str("after", "1 + 2;").unwrap();
+    assert!(matches!(result, ShiroValue::Integer(3)));
+}
+
+#[test]
+fn interrupt_before_eval_is_not_lost() {
+    let mut rt = Runtime::new();
+    rt.interrupt_handle().interrupt();
+    let result = rt.eval_str("loop", "while true { }");
+    assert!(
+        matches!(result, Err(ShiroError::Interrupted)),
+        "{:?}",
+        result
+    );
+
+    // The interrupt is consumed by the evaluation it stopped
+    let result = rt.eval_str("after", "1 + 2;").unwrap();
+    assert!(matches!(result, ShiroValue::Integer(3)));
+}
@@ -0,0 +1,72 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use shiro_interpreter::{Runtime, ShiroValue};
+
+fn format(args: &str) -> Result<String, String> {
+    let mut rt = Runtime::new();
+    let code = format!("import '@std/io' as io;\nio.format({});", args);
+    match rt.eval_str("io_test", &code) {
+        Ok(ShiroValue::String(s)) => Ok(s),
+        Ok(other) => panic!("Expected a string, got {:?}", other),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[test]
+fn format_placeholders() {
+    assert_eq!(
+        format("'{} + {} = {}', 1, 2.5, 'x'").unwrap(),
+        "1 + 2.5 = x"
+    );
+    assert_eq!(
+        format("'[{:>5}|{:<4}|{:^5}]', 42, 'ab', 'c'").unwrap(),
+        "[   42|ab  |  c  ]"
+    );
+    assert_eq!(format("'[{:5}|{:5}]', 7, 'ab'").unwrap(), "[    7|ab   ]");
+    assert_eq!(format("'{:*>4}', 'x'").unwrap(), "***x");
+    assert_eq!(
+        format("'{:.2} {:08.3}', 3.14159, -2.5").unwrap(),
+        "3.14 -002.500"
+    );
+    assert_eq!(format("'{:05} {:.3}', -42, 'abcdef'").unwrap(), "-0042 abc");
+    assert_eq!(format("'{{}} {}', true").unwrap(), "{} true");
+
+    assert!(format("'{} {}', 1").is_err());
+    assert!(format("'{}', 1, 2").is_err());
+    assert!(format("'{', 1").is_err());
+    assert!(format("'{:x}', 1").is_err());
+}
+
+#[test]
+fn reads_stdin_and_writes_both_streams() {
+    let script = "
+import '@std/io' as io;
+io.println('first:', io.readline());
+io.eprint('rest:', len(io.read_all()));
+io.println(io.readline());
+";
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shiro-interpreter"))
+        .args(["eval", "-e", script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run interpreter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"hello\r\nworld\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "first: hello\nnull\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rest: 6");
+}
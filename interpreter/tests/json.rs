@@ -0,0 +1,56 @@
+use shiro_interpreter::{ShiroError, ShiroValue};
+
+mod common;
+
+fn eval(body: &str) -> Result<ShiroValue, ShiroError> {
+    common::eval(&["json"], body)
+}
+
+fn eval_string(body: &str) -> String {
+    common::expect_string(eval(body))
+}
+
+#[test]
+fn round_trip() {
+    let out = eval_string(
+        r#"
+        let data = json.parse('{"name": "shiro", "tags": ["a", "b"], "n": 3, "x": 1.5, "ok": true, "none": null}');
+        json.stringify(data);
+        "#,
+    );
+    assert_eq!(
+        out,
+        r#"{"name":"shiro","tags":["a","b"],"n":3,"x":1.5,"ok":true,"none":null}"#
+    );
+
+    let out =
+        eval_string("let data = json.parse('[1, 2]'); '' + len(data) + ' ' + (data[0] + data[1]);");
+    assert_eq!(out, "2 3");
+}
+
+#[test]
+fn pretty_output() {
+    let out = eval_string("json.stringify({ a: [1], b: {} }, true);");
+    assert_eq!(out, "{\n  \"a\": [\n    1\n  ],\n  \"b\": {}\n}");
+
+    let out = eval_string("json.stringify({ a: 1 }, 4);");
+    assert_eq!(out, "{\n    \"a\": 1\n}");
+}
+
+#[test]
+fn shared_objects_are_not_cycles() {
+    let out = eval_string("let inner = { v: 1 }; json.stringify([inner, inner]);");
+    assert_eq!(out, r#"[{"v":1},{"v":1}]"#);
+
+    let err = eval("let obj = { v: 1 }; obj.self = obj; json.stringify(obj);").unwrap_err();
+    assert!(err.to_string().contains("cyclic"), "{}", err);
+}
+
+#[test]
+fn parse_errors_have_a_position() {
+    let err = eval("json.parse('{\n  \"a\": 1,\n  \"b\" 2\n}');").unwrap_err();
+    match err {
+        ShiroError::JsonError { line, column, .. } => assert_eq!((line, column), (3, 7)),
+        other => panic!("Expected a JSON error, got {:?}", other),
+    }
+}
@@ -0,0 +1,124 @@
+use std::{thread, time::Duration};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+const DEEP_RECURSION: &str = "
+func depth(n) {
+    return 1 + depth(n + 1);
+}
+depth(0);
+";
+
+#[test]
+fn deep_recursion_is_a_stack_overflow() {
+    let mut rt = Runtime::new();
+    rt.max_call_depth = 500;
+    let result = rt.eval_str("recursion", DEEP_RECURSION);
+    assert!(
+        matches!(result, Err(ShiroError::StackOverflow { depth: 500 })),
+        "{:?}",
+        result
+    );
+
+    // The default limit is reached before the native stack runs out
+    let mut rt = Runtime::new();
+    let result = rt.eval_str("recursion", DEEP_RECURSION);
+    assert!(
+        matches!(result, Err(ShiroError::StackOverflow { .. })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn endless_loop_exceeds_the_step_limit() {
+    let mut rt = Runtime::new();
+    rt.max_steps = Some(10_000);
+    let result = rt.eval_str("loop", "while true { }");
+    assert!(
+        matches!(result, Err(ShiroError::StepLimitExceeded { limit: 10_000 })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn endless_loop_times_out() {
+    let mut rt = Runtime::new();
+    rt.timeout = Some(Duration::from_millis(50));
+    let result = rt.eval_str("loop", "while true { }");
+    assert!(
+        matches!(result, Err(ShiroError::Timeout { .. })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn allocating_too_much_exceeds_the_heap_limit() {
+    let mut rt = Runtime::new();
+    rt.heap.set_max_objects(Some(100));
+    let result = rt.eval_str(
+        "alloc",
+        "let items = []; for let i = 0; i < 1000; i += 1 { append(items, {}); }",
+    );
+    assert!(
+        matches!(
+            result,
+            Err(ShiroError::HeapLimitExceeded { limit: 100, .. })
+        ),
+        "{:?}",
+        result
+    );
+
+    let mut rt = Runtime::new();
+    rt.heap.set_max_bytes(Some(10_000));
+    let result = rt.eval_str(
+        "alloc",
+        "let s = 'x'; let items = []; for let i = 0; i < 1000; i += 1 { append(items, s); }",
+    );
+    assert!(
+        matches!(
+            result,
+            Err(ShiroError::HeapLimitExceeded { limit: 10_000, .. })
+        ),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn disabled_libraries_cannot_be_imported() {
+    let mut rt = Runtime::new();
+    rt.libs.disable_lib("@std/os");
+    let result = rt.eval_str("import", "import '@std/os' as os;");
+    assert!(
+        matches!(&result, Err(ShiroError::ModuleDisabled { path }) if path == "@std/os"),
+        "{:?}",
+        result
+    );
+    assert!(rt.eval_str("import", "import '@std/io' as io;").is_ok());
+}
+
+#[test]
+fn host_calls_get_a_fresh_budget() {
+    let mut rt = Runtime::new();
+    rt.max_steps = Some(5_000);
+    rt.timeout = Some(Duration::from_millis(50));
+    rt.eval_str(
+        "setup",
+        "func count(n) { let i = 0; while i < n { i += 1; } return i; } count(400);",
+    )
+    .unwrap();
+    // The deadline of the earlier evaluation has passed by now
+    thread::sleep(Duration::from_millis(100));
+
+    for _ in 0..3 {
+        let result = rt.call_global("count", vec![ShiroValue::Integer(400)]);
+        assert!(
+            matches!(result, Ok(ShiroValue::Integer(400))),
+            "{:?}",
+            result
+        );
+    }
+}
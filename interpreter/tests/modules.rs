@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+use common::project;
+
+fn eval_file(path: PathBuf) -> Result<ShiroValue, ShiroError> {
+    Runtime::new().eval_path(&path.to_string_lossy())
+}
+
+#[test]
+fn modules_are_evaluated_once() {
+    let dir = project(
+        "cache",
+        &[
+            ("main.shiro", "import 'counter' as a; import 'user' as user; a.count += 1; user.read();"),
+            ("user.shiro", "import 'counter' as b; b.count += 10; return { read: func() { return b.count; } };"),
+            ("counter.shiro", "return { count: 0 };"),
+        ],
+    );
+    // Both imports share one export object
+    let result = eval_file(dir.join("main.shiro")).unwrap();
+    assert_eq!(result, ShiroValue::Integer(11));
+}
+
+#[test]
+fn import_cycles_report_the_chain() {
+    let dir = project(
+        "cycle",
+        &[
+            ("main.shiro", "import 'a' as a;"),
+            ("a.shiro", "import 'b' as b; return {};"),
+            ("b.shiro", "import 'a' as a; return {};"),
+        ],
+    );
+    let result = eval_file(dir.join("main.shiro"));
+    let Err(ShiroError::ImportCycle { chain }) = result else {
+        panic!("Expected an import cycle, got {:?}", result);
+    };
+    let expected: Vec<String> = ["a.shiro", "b.shiro", "a.shiro"]
+        .iter()
+        .map(|file| dir.join(file).display().to_string())
+        .collect();
+    assert_eq!(chain, expected);
+
+    // Importing the entry file is a cycle as well
+    let dir = project("self_cycle", &[("main.shiro", "import 'main' as main;")]);
+    let result = eval_file(dir.join("main.shiro"));
+    assert!(
+        matches!(&result, Err(ShiroError::ImportCycle { chain }) if chain.len() == 2),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn imports_resolve_relative_to_the_importing_file() {
+    let dir = project(
+        "relative",
+        &[
+            ("main.shiro", "import 'lib/util' as util; util.name();"),
+            ("lib/util.shiro", "import 'helper' as helper; import '../shared' as shared; return { name: func() { return helper.name + shared.name; } };"),
+            ("lib/helper.shiro", "return { name: 'helper' };"),
+            ("shared.shiro", "return { name: '+shared' };"),
+            // Would be picked if paths were resolved against the entry file
+            ("helper.shiro", "return { name: 'wrong' };"),
+        ],
+    );
+    let result = eval_file(dir.join("main.shiro")).unwrap();
+    assert_eq!(result, ShiroValue::String("helper+shared".to_string()));
+}
+
+#[test]
+fn missing_modules_list_the_tried_paths() {
+    let dir = project("missing", &[("main.shiro", "import 'sub/missing' as m;")]);
+    let result = eval_file(dir.join("main.shiro"));
+    assert!(
+        matches!(
+            &result,
+            Err(ShiroError::ModuleNotFound { path, tried })
+                if path == "sub/missing"
+                    && *tried == [dir.join("sub/missing.shiro").display().to_string()]
+        ),
+        "{:?}",
+        result
+    );
+
+    // Library modules are searched for in every library directory
+    let mut rt = Runtime::new();
+    rt.lib_paths = vec![dir.join("first"), dir.join("second")];
+    let result = rt.eval_str("lib", "import '@std/missing' as m;");
+    let expected = [
+        dir.join("first/@std/missing.shiro").display().to_string(),
+        dir.join("second/@std/missing.shiro").display().to_string(),
+    ];
+    assert!(
+        matches!(
+            &result,
+            Err(ShiroError::ModuleNotFound { path, tried })
+                if path == "@std/missing" && *tried == expected
+        ),
+        "{:?}",
+        result
+    );
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+
+use shiro_interpreter::{Arity, Runtime, ShiroError, ShiroValue};
+
+fn runtime() -> Runtime {
+    let mut rt = Runtime::new();
+    rt.register_typed_function(
+        "shout",
+        |text: String, times: i64| -> Result<String, ShiroError> {
+            Ok(text.to_uppercase().repeat(times.max(0) as usize))
+        },
+    );
+    rt.register_typed_function("sum", |items: Vec<i64>| -> Result<i64, ShiroError> {
+        Ok(items.iter().sum())
+    });
+    rt
+}
+
+#[test]
+fn typed_functions_convert_arguments_and_results() {
+    let mut rt = runtime();
+    let result = rt.eval_str("call", "shout('ab', 2);").unwrap();
+    assert_eq!(result, ShiroValue::String("ABAB".to_string()));
+    let result = rt.eval_str("call", "sum([1, 2, 3]);").unwrap();
+    assert_eq!(result, ShiroValue::Integer(6));
+}
+
+#[test]
+fn wrong_argument_types_name_the_position() {
+    let mut rt = runtime();
+    let result = rt.eval_str("call", "shout('ab', 'twice');");
+    assert!(
+        matches!(
+            &result,
+            Err(ShiroError::ArgumentType { function, position: 2, expected, .. })
+                if function == "shout" && expected == "integer"
+        ),
+        "{:?}",
+        result
+    );
+
+    // Elements of a collection are checked too
+    let result = rt.eval_str("call", "sum([1, 'two']);");
+    assert!(
+        matches!(
+            &result,
+            Err(ShiroError::ArgumentType { function, position: 1, .. }) if function == "sum"
+        ),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn wrong_argument_counts_are_arity_mismatches() {
+    let mut rt = runtime();
+    for code in ["shout('ab');", "shout('ab', 2, 3);"] {
+        let result = rt.eval_str("call", code);
+        assert!(
+            matches!(
+                &result,
+                Err(ShiroError::ArityMismatch { function, .. }) if function == "shout"
+            ),
+            "{}: {:?}",
+            code,
+            result
+        );
+    }
+    let result = rt.eval_str("call", "shout('ab', 2, 3);");
+    assert!(
+        matches!(result, Err(ShiroError::ArityMismatch { got: 3, .. })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn builtins_check_their_arity() {
+    let mut rt = Runtime::new();
+    for (code, name, got) in [("len();", "len", 0), ("append(1);", "append", 1)] {
+        let result = rt.eval_str("call", code);
+        assert!(
+            matches!(
+                &result,
+                Err(ShiroError::ArityMismatch { function, got: n, .. })
+                    if function == name && *n == got
+            ),
+            "{}: {:?}",
+            code,
+            result
+        );
+    }
+}
+
+#[test]
+fn native_closures_capture_host_state() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut rt = Runtime::new();
+    let sink = log.clone();
+    rt.register_native_function("record", Arity::Exact(1), move |args, _| {
+        let mut sink = sink.lock().unwrap();
+        sink.push(args[0].clone());
+        Ok(ShiroValue::Integer(sink.len() as i64))
+    });
+
+    let result = rt
+        .eval_str("call", "record(1); record('two'); record(1 + 2);")
+        .unwrap();
+    assert_eq!(result, ShiroValue::Integer(3));
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            ShiroValue::Integer(1),
+            ShiroValue::String("two".to_string()),
+            ShiroValue::Integer(3)
+        ]
+    );
+}
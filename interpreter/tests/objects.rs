@@ -0,0 +1,37 @@
+mod common;
+
+fn eval_string(code: &str) -> String {
+    common::expect_string(common::eval(&[], code))
+}
+
+#[test]
+fn keys_keep_insertion_order() {
+    let out = eval_string(
+        "
+        let obj = { zebra: 1, apple: 2 };
+        obj.mango = 3;
+        obj['banana'] = 4;
+        obj.zebra = 5;
+        let out = '';
+        for key in keys(obj) { out += key + ','; }
+        for value in values(obj) { out += value; out += ','; }
+        out;
+        ",
+    );
+    assert_eq!(out, "zebra,apple,mango,banana,5,2,3,4,");
+}
+
+#[test]
+fn for_in_visits_items_in_order() {
+    let out = eval_string(
+        "
+        let obj = { c: 1, a: 2, b: 3 };
+        let out = '';
+        for key in obj { out += key; }
+        for item in [3, 1, 2] { out += item; }
+        for chr in 'xyz' { out += chr; }
+        out;
+        ",
+    );
+    assert_eq!(out, "cab312xyz");
+}
@@ -0,0 +1,116 @@
+use std::{
+    env,
+    process::{self, Command},
+};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+fn eval(rt: &mut Runtime, expr: &str) -> Result<ShiroValue, ShiroError> {
+    common::eval_in(rt, &["os"], &format!("{};", expr))
+}
+
+#[test]
+fn process_information() {
+    let mut rt = Runtime::new();
+    rt.args = vec!["one".to_string(), "two".to_string()];
+    assert_eq!(
+        eval(&mut rt, "let args = os.args(); args[1]").unwrap(),
+        ShiroValue::String("two".to_string())
+    );
+    assert_eq!(
+        eval(&mut rt, "os.platform()").unwrap(),
+        ShiroValue::String(env::consts::OS.to_string())
+    );
+    assert_eq!(
+        eval(&mut rt, "os.pid()").unwrap(),
+        ShiroValue::Integer(process::id() as i64)
+    );
+    assert_eq!(
+        eval(&mut rt, "os.cwd()").unwrap(),
+        ShiroValue::String(env::current_dir().unwrap().to_string_lossy().into_owned())
+    );
+    assert!(
+        matches!(eval(&mut rt, "os.hostname()").unwrap(), ShiroValue::String(name) if !name.is_empty())
+    );
+    assert_eq!(
+        eval(&mut rt, "os.getenv('SHIRO_TEST_SURELY_UNSET')").unwrap(),
+        ShiroValue::String(String::new())
+    );
+}
+
+#[test]
+fn exit_codes() {
+    let mut rt = Runtime::new();
+    for (expr, expected) in [
+        ("os.exit()", 0),
+        ("os.exit(3)", 3),
+        ("os.exit(-1)", -1),
+        ("os.exit(1099511627776)", 1),
+    ] {
+        let result = eval(&mut rt, expr);
+        assert!(
+            matches!(result, Err(ShiroError::Exit { code }) if code == expected),
+            "{}: {:?}",
+            expr,
+            result
+        );
+    }
+}
+
+#[test]
+fn process_changes_are_off_by_default() {
+    let mut rt = Runtime::new();
+    for expr in [
+        "os.setenv('SHIRO_TEST_VAR', 'x')",
+        "os.unsetenv('SHIRO_TEST_VAR')",
+        "os.chdir('/')",
+    ] {
+        let result = eval(&mut rt, expr);
+        assert!(
+            matches!(&result, Err(ShiroError::GenericRuntimeError(message)) if message.contains("not allowed")),
+            "{}: {:?}",
+            expr,
+            result
+        );
+    }
+}
+
+fn shiro(code: &str) -> process::Output {
+    Command::new(env!("CARGO_BIN_EXE_shiro-interpreter"))
+        .args(["eval", "-e", &format!("import '@std/os' as os; {}", code)])
+        .output()
+        .expect("Failed to run interpreter")
+}
+
+// The command line tool allows process changes, and running it in a process
+// of its own keeps them away from the other tests
+#[test]
+fn scripts_change_the_process_from_the_command_line() {
+    let output = shiro(
+        "os.setenv('SHIRO_TEST_VAR', 'set'); let a = os.getenv('SHIRO_TEST_VAR'); os.unsetenv('SHIRO_TEST_VAR'); a + '|' + os.getenv('SHIRO_TEST_VAR');",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "set|");
+
+    let dir = env::temp_dir().canonicalize().unwrap();
+    let output = shiro(&format!("os.chdir('{}'); os.cwd();", dir.display()));
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        dir.to_string_lossy()
+    );
+
+    let output = shiro("os.chdir('/surely/missing/dir');");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("E0315"));
+}
+
+#[test]
+fn exit_statuses_from_the_command_line() {
+    for (code, expected) in [("0", 0), ("3", 3), ("255", 255), ("256", 1), ("-1", 1)] {
+        let output = shiro(&format!("os.exit({});", code));
+        assert_eq!(output.status.code(), Some(expected), "os.exit({})", code);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fs, path::Path};
+
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+use common::project;
+
+fn run(dir: &Path) -> Result<ShiroValue, ShiroError> {
+    Runtime::new().eval_package(&dir.join("shiro.toml"))
+}
+
+const APP_MANIFEST: &str = "
+[package]
+name = 'app'
+version = '0.1.0'
+
+[dependencies]
+greet = '1.0.0'
+";
+
+const GREET_MANIFEST: &str = "
+[package]
+name = 'greet'
+version = '1.0.0'
+";
+
+#[test]
+fn packages_import_their_dependencies() {
+    let dir = project(
+        "imports",
+        &[
+            ("shiro.toml", APP_MANIFEST),
+            (
+                "main.shiro",
+                "import 'greet' as greet; import 'greet/extra' as extra; greet.name + extra.name;",
+            ),
+            ("vendor/greet/shiro.toml", GREET_MANIFEST),
+            ("vendor/greet/main.shiro", "return { name: 'main' };"),
+            ("vendor/greet/extra.shiro", "return { name: '+extra' };"),
+        ],
+    );
+    let result = run(&dir).unwrap();
+    assert_eq!(result, ShiroValue::String("main+extra".to_string()));
+
+    let lock = fs::read_to_string(dir.join("shiro.lock")).unwrap();
+    assert!(lock.contains("name = \"greet\""), "{}", lock);
+    assert!(lock.contains("version = \"1.0.0\""), "{}", lock);
+}
+
+#[test]
+fn invalid_manifests_are_manifest_errors() {
+    let cases = [
+        ("missing_package", "[dependencies]\n"),
+        ("missing_version", "[package]\nname = 'app'\n"),
+        ("bad_toml", "[package\n"),
+        (
+            "bad_dependency",
+            "[package]\nname = 'app'\nversion = '0.1.0'\n[dependencies]\nx = 1\n",
+        ),
+        (
+            "missing_dependency",
+            "[package]\nname = 'app'\nversion = '0.1.0'\n[dependencies]\nnope = '1.0.0'\n",
+        ),
+    ];
+    for (name, manifest) in cases {
+        let dir = project(name, &[("shiro.toml", manifest), ("main.shiro", "1;")]);
+        let result = run(&dir);
+        assert!(
+            matches!(result, Err(ShiroError::ManifestError { .. })),
+            "{}: {:?}",
+            name,
+            result
+        );
+    }
+}
+
+#[test]
+fn vendored_versions_must_match() {
+    let dir = project(
+        "version",
+        &[
+            (
+                "shiro.toml",
+                APP_MANIFEST.replace("1.0.0", "2.0.0").as_str(),
+            ),
+            ("main.shiro", "1;"),
+            ("vendor/greet/shiro.toml", GREET_MANIFEST),
+            ("vendor/greet/main.shiro", "return {};"),
+        ],
+    );
+    let result = run(&dir);
+    assert!(
+        matches!(&result, Err(ShiroError::ManifestError { message, .. }) if message.contains("2.0.0")),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn lockfile_pins_resolved_versions() {
+    let dir = project(
+        "lock",
+        &[
+            (
+                "shiro.toml",
+                APP_MANIFEST
+                    .replace("'1.0.0'", "{ path = 'greet' }")
+                    .as_str(),
+            ),
+            ("main.shiro", "import 'greet' as greet; greet.version;"),
+            ("greet/shiro.toml", GREET_MANIFEST),
+            ("greet/main.shiro", "return { version: 1 };"),
+        ],
+    );
+    assert_eq!(run(&dir).unwrap(), ShiroValue::Integer(1));
+
+    // The dependency changes its version behind the lockfile's back
+    fs::write(
+        dir.join("greet/shiro.toml"),
+        GREET_MANIFEST.replace("1.0.0", "1.1.0"),
+    )
+    .unwrap();
+    let result = run(&dir);
+    assert!(
+        matches!(
+            &result,
+            Err(ShiroError::ManifestError { path, message })
+                if path.ends_with("shiro.lock") && message.contains("1.1.0")
+        ),
+        "{:?}",
+        result
+    );
+
+    // Removing the lockfile accepts the new version
+    fs::remove_file(dir.join("shiro.lock")).unwrap();
+    assert_eq!(run(&dir).unwrap(), ShiroValue::Integer(1));
+    let lock = fs::read_to_string(dir.join("shiro.lock")).unwrap();
+    assert!(lock.contains("1.1.0"), "{}", lock);
+}
+
+#[test]
+fn unchanged_lockfile_is_left_alone() {
+    let dir = project(
+        "lock-unchanged",
+        &[
+            (
+                "shiro.toml",
+                APP_MANIFEST
+                    .replace("'1.0.0'", "{ path = 'greet' }")
+                    .as_str(),
+            ),
+            ("main.shiro", "import 'greet' as greet; greet.version;"),
+            ("greet/shiro.toml", GREET_MANIFEST),
+            ("greet/main.shiro", "return { version: 1 };"),
+        ],
+    );
+    let lock_path = dir.join("shiro.lock");
+    run(&dir).unwrap();
+    let lock = fs::read_to_string(&lock_path).unwrap();
+    let modified = fs::metadata(&lock_path).unwrap().modified().unwrap();
+
+    run(&dir).unwrap();
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), lock);
+    assert_eq!(
+        fs::metadata(&lock_path).unwrap().modified().unwrap(),
+        modified
+    );
+
+    // Only the locked packages matter, not how the file is written
+    let edited = format!("# Checked in by hand\n{}", lock);
+    fs::write(&lock_path, &edited).unwrap();
+    run(&dir).unwrap();
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), edited);
+}
@@ -0,0 +1,85 @@
+use shiro_interpreter::ShiroValue;
+
+mod common;
+
+fn eval(expr: &str) -> ShiroValue {
+    common::eval(&["path"], &format!("{};", expr)).expect("Script failed")
+}
+
+fn eval_string(expr: &str) -> String {
+    common::expect_string(common::eval(&["path"], &format!("{};", expr)))
+}
+
+#[test]
+fn components() {
+    assert_eq!(eval_string("path.join('a', 'b', 'c.txt')"), "a/b/c.txt");
+    assert_eq!(eval_string("path.join('a/', 'b')"), "a/b");
+    assert_eq!(eval_string("path.join('a', '/etc')"), "/etc");
+
+    assert_eq!(eval_string("path.dirname('a/b/c.txt')"), "a/b");
+    assert_eq!(eval_string("path.dirname('a/b/')"), "a");
+    assert_eq!(eval_string("path.dirname('file')"), "");
+    assert_eq!(eval_string("path.dirname('/')"), "");
+
+    assert_eq!(eval_string("path.basename('a/b/c.txt')"), "c.txt");
+    assert_eq!(eval_string("path.basename('a/b/')"), "b");
+    assert_eq!(eval_string("path.basename('a/..')"), "");
+
+    assert_eq!(eval_string("path.extension('a/c.tar.gz')"), "gz");
+    assert_eq!(eval_string("path.extension('.bashrc')"), "");
+    assert_eq!(eval_string("path.stem('a/c.tar.gz')"), "c.tar");
+    assert_eq!(eval_string("path.stem('.bashrc')"), ".bashrc");
+}
+
+#[test]
+fn normalize() {
+    assert_eq!(eval_string("path.normalize('a/./b/../c/')"), "a/c");
+    assert_eq!(eval_string("path.normalize('../a/../../b')"), "../../b");
+    assert_eq!(eval_string("path.normalize('/../a')"), "/a");
+    assert_eq!(eval_string("path.normalize('a/..')"), ".");
+    assert_eq!(eval_string("path.normalize('')"), ".");
+}
+
+#[test]
+fn absolute_and_relative() {
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        eval_string("path.absolute('x/../y')"),
+        cwd.join("y").to_string_lossy()
+    );
+    assert_eq!(eval_string("path.absolute('/a/b/..')"), "/a");
+
+    assert_eq!(eval_string("path.relative('/a/b/c', '/a')"), "b/c");
+    assert_eq!(eval_string("path.relative('/a', '/a/b/c')"), "../..");
+    assert_eq!(eval_string("path.relative('/a/x', '/a/b/')"), "../x");
+    assert_eq!(eval_string("path.relative('/a', '/a')"), ".");
+    assert_eq!(eval_string("path.relative('y')"), "y");
+
+    assert!(matches!(
+        eval("path.is_absolute('/a')"),
+        ShiroValue::Boolean(true)
+    ));
+    assert!(matches!(
+        eval("path.is_absolute('a/b')"),
+        ShiroValue::Boolean(false)
+    ));
+}
+
+#[test]
+fn separators_and_parent_directories() {
+    assert_eq!(eval_string("path.join('a', '..', 'b')"), "a/../b");
+    assert_eq!(eval_string("path.join('a', '')"), "a/");
+    assert_eq!(eval_string("path.normalize('a//b///c')"), "a/b/c");
+    assert_eq!(eval_string("path.normalize('./a/./')"), "a");
+    assert_eq!(eval_string("path.normalize('/a/b/../../..')"), "/");
+
+    assert_eq!(eval_string("path.basename('a/b.txt/')"), "b.txt");
+    assert_eq!(eval_string("path.extension('a/b.txt/')"), "txt");
+    assert_eq!(eval_string("path.stem('a/b.txt/')"), "b");
+    assert_eq!(eval_string("path.extension('a/b.')"), "");
+    assert_eq!(eval_string("path.dirname('../a')"), "..");
+    assert_eq!(eval_string("path.basename('..')"), "");
+
+    assert_eq!(eval_string("path.relative('/a/b/../c', '/a/./c')"), ".");
+    assert_eq!(eval_string("path.relative('/a/b/', '/a/c/')"), "../b");
+}
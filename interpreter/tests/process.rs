@@ -0,0 +1,122 @@
+#![cfg(unix)]
+
+use shiro_interpreter::{Runtime, ShiroValue};
+
+mod common;
+
+fn eval_string(body: &str) -> String {
+    common::expect_string(common::eval(&["process"], body))
+}
+
+#[test]
+fn run_captures_output_and_status() {
+    let out = eval_string(
+        "
+        let result = process.run('/bin/sh', ['-c', 'echo out; echo err >&2; exit 3']);
+        result.stdout + '|' + result.stderr + '|' + result.status + '|' + result.success;
+        ",
+    );
+    assert_eq!(out, "out\n|err\n|3|false");
+}
+
+#[test]
+fn run_with_env_cwd_and_stdin() {
+    let out = eval_string(
+        "
+        let result = process.run('/bin/sh', ['-c', 'echo $GREETING; pwd'], {
+            env: { GREETING: 'hello' },
+            cwd: '/'
+        });
+        result.stdout;
+        ",
+    );
+    assert_eq!(out, "hello\n/\n");
+
+    let out = eval_string(
+        "let result = process.run('cat', [], { stdin: 'piped\ninput' }); result.stdout;",
+    );
+    assert_eq!(out, "piped\ninput");
+}
+
+#[test]
+fn spawn_streams_lines() {
+    let out = eval_string(
+        "
+        let cat = process.spawn('cat');
+        cat.write('one\n');
+        let first = cat.read_line();
+        cat.write('two\nthree\n');
+        cat.close_stdin();
+        let rest = [];
+        let line = cat.read_line();
+        while typeof(line) != 'null' {
+            append(rest, line);
+            line = cat.read_line();
+        }
+        first + ':' + rest[0] + ',' + rest[1] + ':' + cat.wait();
+        ",
+    );
+    assert_eq!(out, "one:two,three:0");
+}
+
+#[test]
+fn spawn_failure_is_an_io_error() {
+    let mut rt = Runtime::new();
+    let err = rt
+        .eval_str(
+            "process_test",
+            "import '@std/process' as process; process.run('/nonexistent/tool');",
+        )
+        .unwrap_err();
+    assert_eq!(err.error_code(), "E0315");
+}
+
+#[test]
+fn spawn_feeds_large_input_without_blocking() {
+    // Far more than a pipe buffer holds, so writing it all before reading
+    // would block on a child that echoes its input
+    let input = "line of input\n".repeat(100_000);
+    let mut rt = Runtime::new();
+    rt.set_global("input", ShiroValue::String(input.clone()));
+    let result = rt
+        .eval_str(
+            "process_test",
+            "
+            import '@std/process' as process;
+            let cat = process.spawn('cat', [], { stdin: input });
+            let out = cat.read_all();
+            let status = cat.wait();
+            [out, status];
+            ",
+        )
+        .expect("Script failed");
+    let ShiroValue::HeapRef(addr) = result else {
+        panic!("Expected an array, got {:?}", result);
+    };
+    let values = rt.heap.deref(addr).unwrap().borrow().values();
+    assert!(matches!(&values[0], ShiroValue::String(out) if *out == input));
+    assert!(matches!(values[1], ShiroValue::Integer(0)));
+
+    // Stdin is closed once the input is written
+    let err = rt
+        .eval_str(
+            "process_test",
+            "let cat = process.spawn('cat', [], { stdin: 'x' }); cat.write('more');",
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("closed stdin"), "{}", err);
+}
+
+#[test]
+fn wait_buffers_unread_output() {
+    // Both streams get more than a pipe buffer holds; the child can only
+    // exit if something keeps reading them while the script waits
+    let out = eval_string(
+        "
+        let child = process.spawn('/bin/sh', ['-c', 'head -c 200000 /dev/zero; head -c 100000 /dev/zero >&2']);
+        let status = child.wait();
+        '' + status + ':' + len(child.read_all()) + ':' + len(child.read_stderr());
+        ",
+    );
+    assert_eq!(out, "0:200000:100000");
+}
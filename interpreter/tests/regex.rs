@@ -0,0 +1,102 @@
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+fn eval(body: &str) -> Result<ShiroValue, ShiroError> {
+    common::eval(&["regex", "string"], body)
+}
+
+fn eval_string(body: &str) -> String {
+    common::expect_string(eval(body))
+}
+
+#[test]
+fn compiled_patterns() {
+    assert_eq!(
+        eval_string(
+            "let re = regex.compile('\\d+'); '' + re.is_match('a1') + re.is_match('ab') + ' ' + typeof(re) + ' ' + re.source();"
+        ),
+        "truefalse Regex \\d+"
+    );
+    assert_eq!(
+        eval_string(
+            "let re = regex.compile('b+'); let m = re.find('aabbbc'); m.text + m.start + m.end;"
+        ),
+        "bbb25"
+    );
+    assert!(matches!(
+        eval("let re = regex.compile('z'); re.find('abc');").unwrap(),
+        ShiroValue::Null
+    ));
+    assert_eq!(
+        eval_string(
+            "let re = regex.compile('[a-z]\\d'); let found = []; for m in re.find_all('a1 b2 cc d3') { append(found, m.text); } string.join(found, ',');"
+        ),
+        "a1,b2,d3"
+    );
+}
+
+#[test]
+fn captures_with_named_groups() {
+    assert_eq!(
+        eval_string(
+            "let caps = regex.captures('(?P<key>\\w+)=(?P<value>\\w+)(;)?', 'name=shiro'); caps.key + ':' + caps.value + ':' + caps[0] + ':' + caps[1] + ':' + caps[3];"
+        ),
+        "name:shiro:name=shiro:name:null"
+    );
+}
+
+#[test]
+fn replace_and_split() {
+    assert_eq!(
+        eval_string(
+            "regex.replace('\\d', 'a1b2', '*') + ' ' + regex.replace_all('\\d', 'a1b2', '*');"
+        ),
+        "a*b2 a*b*"
+    );
+    assert_eq!(
+        eval_string("regex.replace_all('(\\w+)@(\\w+)', 'me@host', '$2 at $1');"),
+        "host at me"
+    );
+    assert_eq!(
+        eval_string("string.join(regex.split('\\s*,\\s*', 'a , b,c'), '|');"),
+        "a|b|c"
+    );
+}
+
+#[test]
+fn invalid_pattern_is_an_error() {
+    let err = eval("regex.compile('(unclosed');").unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid regular expression"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn find_all_reports_character_positions() {
+    let mut rt = Runtime::new();
+    rt.set_global("text", ShiroValue::String("ä1 ö22 ü333".to_string()));
+    let result = rt
+        .eval_str(
+            "regex_test",
+            "import '@std/regex' as regex; let out = ''; for m in regex.find_all('\\d+', text) { out += '' + m.start + '-' + m.end + ' '; } out;",
+        )
+        .unwrap();
+    assert_eq!(result, ShiroValue::String("1-2 4-6 8-11 ".to_string()));
+
+    // Many matches in a long text stay fast
+    let count = 50_000;
+    rt.set_global("text", ShiroValue::String("é1 ".repeat(count)));
+    let result = rt
+        .eval_str(
+            "regex_test",
+            "let all = regex.find_all('\\d', text); let last = all[len(all) - 1]; '' + len(all) + ':' + last.start;",
+        )
+        .unwrap();
+    assert_eq!(
+        result,
+        ShiroValue::String(format!("{}:{}", count, 3 * (count - 1) + 1))
+    );
+}
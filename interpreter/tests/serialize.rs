@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use shiro_interpreter::{from_value, to_value, Heap, Runtime, ShiroError, ShiroValue};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Line(i64, i64),
+    Rect { width: i64, height: i64 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Drawing {
+    name: String,
+    layer: Option<u8>,
+    note: Option<String>,
+    shapes: Vec<Shape>,
+    tags: BTreeMap<String, bool>,
+}
+
+fn round_trip<T>(value: &T) -> T
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let mut heap = Heap::new();
+    let shiro = to_value(value, &mut heap).expect("to_value failed");
+    from_value(&shiro, &heap).expect("from_value failed")
+}
+
+#[test]
+fn structs_and_enums_round_trip() {
+    let drawing = Drawing {
+        name: "sketch".to_string(),
+        layer: Some(3),
+        note: None,
+        shapes: vec![
+            Shape::Point,
+            Shape::Circle(1.5),
+            Shape::Line(-1, 2),
+            Shape::Rect {
+                width: 4,
+                height: 5,
+            },
+        ],
+        tags: BTreeMap::from([("draft".to_string(), true), ("shared".to_string(), false)]),
+    };
+    assert_eq!(round_trip(&drawing), drawing);
+}
+
+#[test]
+fn options_sequences_and_maps_round_trip() {
+    assert_eq!(round_trip(&Some(7i64)), Some(7));
+    assert_eq!(round_trip(&None::<i64>), None);
+    assert_eq!(
+        round_trip(&vec![vec![1i64], vec![], vec![2, 3]]),
+        vec![vec![1], vec![], vec![2, 3]]
+    );
+    let map = BTreeMap::from([(1i64, "one".to_string()), (2, "two".to_string())]);
+    let mut heap = Heap::new();
+    let value = to_value(&map, &mut heap).unwrap();
+    // Integer keys become strings in an object
+    let back: BTreeMap<String, String> = from_value(&value, &heap).unwrap();
+    assert_eq!(back["1"], "one");
+    assert_eq!(back["2"], "two");
+}
+
+#[test]
+fn script_values_convert_into_rust_types() {
+    let mut rt = Runtime::new();
+    let value = rt
+        .eval_str(
+            "drawing",
+            "{ name: 'plan', layer: 1, note: 'wip', shapes: ['Point', { Circle: 2.0 }], tags: {} };",
+        )
+        .unwrap();
+    let drawing: Drawing = from_value(&value, &rt.heap).unwrap();
+    assert_eq!(drawing.name, "plan");
+    assert_eq!(drawing.layer, Some(1));
+    assert_eq!(drawing.note.as_deref(), Some("wip"));
+    assert_eq!(drawing.shapes, vec![Shape::Point, Shape::Circle(2.0)]);
+}
+
+#[test]
+fn out_of_range_integers_are_conversion_errors() {
+    let mut heap = Heap::new();
+    let result = from_value::<u8>(&ShiroValue::Integer(300), &heap);
+    assert!(
+        matches!(result, Err(ShiroError::ConversionError(_))),
+        "{:?}",
+        result
+    );
+    let result = from_value::<u32>(&ShiroValue::Integer(-1), &heap);
+    assert!(
+        matches!(result, Err(ShiroError::ConversionError(_))),
+        "{:?}",
+        result
+    );
+    let result = to_value(&u64::MAX, &mut heap);
+    assert!(
+        matches!(result, Err(ShiroError::ConversionError(_))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn cycles_are_conversion_errors() {
+    let mut rt = Runtime::new();
+    let value = rt
+        .eval_str("cycle", "let o = { name: 'loop' }; o.self = o; o;")
+        .unwrap();
+    let result = from_value::<serde::de::IgnoredAny>(&value, &rt.heap);
+    assert!(
+        matches!(&result, Err(ShiroError::ConversionError(message)) if message.contains("cyclic")),
+        "{:?}",
+        result
+    );
+
+    // An object that appears twice without a cycle is fine
+    let value = rt.eval_str("shared", "let p = { x: 1 }; [p, p];").unwrap();
+    let points: Vec<BTreeMap<String, i64>> = from_value(&value, &rt.heap).unwrap();
+    assert_eq!(points.len(), 2);
+}
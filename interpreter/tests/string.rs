@@ -0,0 +1,111 @@
+use shiro_interpreter::{Runtime, ShiroError, ShiroValue};
+
+mod common;
+
+/// Evaluates `body` with `word` bound to a string holding non-ASCII
+/// characters, which script literals cannot contain.
+fn eval_string(body: &str) -> String {
+    let mut rt = Runtime::new();
+    rt.set_global("word", ShiroValue::String("héllo".to_string()));
+    common::expect_string(common::eval_in(&mut rt, &["string"], body))
+}
+
+#[test]
+fn module_functions() {
+    assert_eq!(
+        eval_string("string.join(string.split('a,b,,c', ','), '|');"),
+        "a|b||c"
+    );
+    assert_eq!(
+        eval_string("string.join(string.split('  a \tb  c '), '|');"),
+        "a|b|c"
+    );
+    assert_eq!(eval_string("string.join([1, 'x', true]);"), "1xtrue");
+    assert_eq!(eval_string("'[' + string.trim('  hi \n') + ']';"), "[hi]");
+    assert_eq!(
+        eval_string("string.replace('aXbXc', 'X', '--');"),
+        "a--b--c"
+    );
+    assert_eq!(eval_string("'' + string.find(word, 'llo');"), "2");
+    assert_eq!(eval_string("'' + string.find(word, 'z');"), "-1");
+    assert_eq!(
+        eval_string("'' + string.starts_with('shiro', 'sh') + string.ends_with('shiro', 'x');"),
+        "truefalse"
+    );
+    assert_eq!(
+        eval_string("string.upper(word) + string.lower('DEF');"),
+        "HÉLLOdef"
+    );
+    assert_eq!(eval_string("string.repeat('ab', 3);"), "ababab");
+    assert_eq!(
+        eval_string("string.pad_left('7', 3, '0') + '|' + string.pad_right(word, 6) + '|';"),
+        "007|héllo |"
+    );
+    assert_eq!(eval_string("string.pad_left('long', 2);"), "long");
+    assert_eq!(
+        eval_string(
+            "string.substring(word, 1, 3) + '|' + string.substring(word, 3) + '|' + string.substring('hi', 5);"
+        ),
+        "él|lo|"
+    );
+    assert_eq!(
+        eval_string("string.join(string.chars(word), ',');"),
+        "h,é,l,l,o"
+    );
+}
+
+#[test]
+fn methods_on_strings() {
+    assert_eq!(
+        eval_string(
+            "let s = 'a, b ,c'; let parts = s.split(','); let mid = parts[1]; let last = parts[2]; mid.trim() + last.upper();"
+        ),
+        "bC"
+    );
+    assert_eq!(
+        eval_string("word.substring(1, 3) + word.pad_left(7, '*');"),
+        "él**héllo"
+    );
+    // Indexing a string still yields its characters
+    assert_eq!(eval_string("let s = 'abc'; '' + s[1];"), "b");
+    assert_eq!(eval_string("let s = 'abc'; '' + s['0'] + s['2'];"), "ac");
+
+    // The characters of a string are the values indexing yields
+    assert_eq!(
+        eval_string(
+            "
+            let cs = word.chars();
+            let same = true;
+            for let i = 0; i < len(cs); i += 1 {
+                same = same && cs[i] == word[i] && typeof(cs[i]) == typeof(word[i]);
+            }
+            '' + same + ' ' + typeof(cs[1]);
+            "
+        ),
+        "true char"
+    );
+    assert_eq!(
+        eval_string("let s = 'abc'; '' + typeof(s['7']) + typeof(s['nope']);"),
+        "nullnull"
+    );
+}
+
+#[test]
+fn oversized_results_are_errors() {
+    for body in [
+        "string.repeat('abc', 9223372036854775807);",
+        "let s = 'ab'; s.repeat(1073741824);",
+        "string.pad_left('x', 9223372036854775807);",
+        "let s = 'x'; s.pad_right(2000000000, '*');",
+    ] {
+        let mut rt = Runtime::new();
+        let code = format!("import '@std/string' as string;\n{}", body);
+        let result = rt.eval_str("string_test", &code);
+        assert!(
+            matches!(&result, Err(ShiroError::GenericRuntimeError(message)) if message.contains("too large")),
+            "{}: {:?}",
+            body,
+            result
+        );
+    }
+}
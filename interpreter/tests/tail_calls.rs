@@ -0,0 +1,18 @@
+use std::process::Command;
+
+#[test]
+fn tail_recursion_runs_in_constant_stack() {
+    let output = Command::new(env!("CARGO_BIN_EXE_shiro-interpreter"))
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../examples/tail_calls.shiro"
+        ))
+        .output()
+        .expect("Failed to run interpreter");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec!["done", "false"]);
+}
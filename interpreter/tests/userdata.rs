@@ -0,0 +1,97 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, Ordering},
+    Arc,
+};
+
+use shiro_interpreter::{Arity, Runtime, ShiroError, ShiroValue, UserData, UserDataType};
+
+struct Counter {
+    count: AtomicI64,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A runtime with a global `counter()` that creates a `Counter`, and a flag
+/// that is set once the last counter is dropped.
+fn runtime() -> (Runtime, Arc<AtomicBool>) {
+    let ty = Arc::new(
+        UserDataType::new("Counter")
+            .method("add", Arity::Exact(1), |this, args, _| {
+                let counter = this.downcast::<Counter>()?;
+                let ShiroValue::Integer(n) = args[0] else {
+                    return Err(ShiroError::GenericRuntimeError(
+                        "Expected an integer".to_string(),
+                    ));
+                };
+                Ok(ShiroValue::Integer(
+                    counter.count.fetch_add(n, Ordering::SeqCst) + n,
+                ))
+            })
+            .method("get", Arity::Exact(0), |this, _, _| {
+                let counter = this.downcast::<Counter>()?;
+                Ok(ShiroValue::Integer(counter.count.load(Ordering::SeqCst)))
+            }),
+    );
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = dropped.clone();
+
+    let mut rt = Runtime::new();
+    rt.register_native_function("counter", Arity::Exact(0), move |_, ctx| {
+        let data = UserData::new(
+            &ty,
+            Counter {
+                count: AtomicI64::new(0),
+                dropped: flag.clone(),
+            },
+        );
+        ctx.heap.alloc_userdata(data)
+    });
+    (rt, dropped)
+}
+
+#[test]
+fn typeof_reports_the_type_name() {
+    let (mut rt, _) = runtime();
+    let result = rt.eval_str("typeof", "typeof(counter());").unwrap();
+    assert_eq!(result, ShiroValue::String("Counter".to_string()));
+}
+
+#[test]
+fn methods_share_the_host_value() {
+    let (mut rt, _) = runtime();
+    let result = rt
+        .eval_str("methods", "let c = counter(); c.add(2); c.add(3); c.get();")
+        .unwrap();
+    assert_eq!(result, ShiroValue::Integer(5));
+
+    // The state persists across evaluations while the object is reachable
+    let result = rt.eval_str("again", "c.add(1);").unwrap();
+    assert_eq!(result, ShiroValue::Integer(6));
+
+    let result = rt.eval_str("arity", "c.add();");
+    assert!(
+        matches!(result, Err(ShiroError::ArityMismatch { .. })),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn collected_userdata_is_dropped() {
+    let (mut rt, dropped) = runtime();
+    rt.eval_str("create", "let c = counter(); let get = c.get;")
+        .unwrap();
+    rt.eval_str("collect", "c = null;").unwrap();
+    // A bound method still refers to the value
+    assert!(!dropped.load(Ordering::SeqCst));
+    let result = rt.eval_str("call", "get();").unwrap();
+    assert_eq!(result, ShiroValue::Integer(0));
+
+    rt.eval_str("release", "get = null;").unwrap();
+    assert!(dropped.load(Ordering::SeqCst));
+}